@@ -0,0 +1,19 @@
+use anyhow::Result;
+use log::debug;
+use simple_logger::SimpleLogger;
+use BGX13P_lib_rust::discovery::{discover_modules, DiscoveryConfig};
+
+fn main() -> Result<()> {
+    SimpleLogger::new().init().unwrap();
+
+    let report = discover_modules(&DiscoveryConfig::default())?;
+
+    for m in report.accepted {
+        debug!("{:#?}", m);
+    }
+    for r in report.rejected {
+        debug!("{} rejected: {}", r.port_name, r.reason);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,21 @@
+use anyhow::Result;
+use log::debug;
+use simple_logger::SimpleLogger;
+use std::{thread::sleep, time::Duration};
+use BGX13P_lib_rust::hotplug::{HotplugOptions, HotplugWatcher};
+
+fn main() -> Result<()> {
+    SimpleLogger::new().init().unwrap();
+
+    let watcher = HotplugWatcher::start(
+        HotplugOptions {
+            reach_well_known_state: true,
+            ..Default::default()
+        },
+        |e| debug!("{:#?}", e),
+    );
+
+    sleep(Duration::from_secs(120));
+
+    watcher.stop()
+}
@@ -0,0 +1,28 @@
+use anyhow::Result;
+use log::debug;
+use simple_logger::SimpleLogger;
+use BGX13P_lib_rust::{
+    advertising::{AdvertisingMode, AdvertisingSettings},
+    bgx::detect_modules,
+};
+
+fn main() -> Result<()> {
+    SimpleLogger::new().init().unwrap();
+
+    if let Some(bgx) = detect_modules().unwrap().first_mut() {
+        bgx.reach_well_known_state()?;
+
+        bgx.set_advertising_settings(&AdvertisingSettings::default())?;
+        bgx.start_advertising(AdvertisingMode::High)?;
+
+        let central = bgx.wait_for_connection(None)?;
+        debug!("Central {} connected", central);
+
+        let data = bgx.read_all_with_timeout(None)?;
+        debug!("Received: {:?}", data);
+
+        Ok(())
+    } else {
+        Err(anyhow::anyhow!("Couldn't apply settings"))
+    }
+}
@@ -0,0 +1,18 @@
+use anyhow::Result;
+use simple_logger::SimpleLogger;
+use std::time::Duration;
+use BGX13P_lib_rust::{bgx::detect_modules, pty_bridge::VirtualPort};
+
+fn main() -> Result<()> {
+    SimpleLogger::new().init().unwrap();
+
+    if let Some(mut bgx) = detect_modules().unwrap().pop() {
+        bgx.reach_well_known_state()?;
+
+        // applications open /tmp/ttyBGX as if it was a local serial port
+        let mut port = VirtualPort::create(Some("/tmp/ttyBGX".into()))?;
+        bgx.serve_pty(&mut port, &"d0cf5e828506".parse()?, Duration::from_secs(5))
+    } else {
+        Err(anyhow::anyhow!("Couldn't find a BGX module"))
+    }
+}
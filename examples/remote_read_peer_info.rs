@@ -0,0 +1,27 @@
+use anyhow::Result;
+use log::debug;
+use simple_logger::SimpleLogger;
+use BGX13P_lib_rust::bgx::detect_modules;
+
+fn main() -> Result<()> {
+    SimpleLogger::new().init().unwrap();
+
+    if let Some(bgx) = detect_modules().unwrap().first_mut() {
+        bgx.reach_well_known_state()?;
+
+        bgx.connect(&"d0cf5e828506".parse().unwrap())?;
+
+        {
+            let mut remote = bgx.remote()?;
+            debug!("Peer FW: {}", remote.version()?);
+            debug!("Peer name: {}", remote.name()?);
+            debug!("Peer UART baud rate: {}", remote.get("ua b")?);
+        }
+
+        bgx.disconnect()?;
+
+        Ok(())
+    } else {
+        Err(anyhow::anyhow!("Couldn't apply settings"))
+    }
+}
@@ -0,0 +1,32 @@
+use anyhow::Result;
+use log::debug;
+use simple_logger::SimpleLogger;
+use std::{thread::sleep, time::Duration};
+use BGX13P_lib_rust::{
+    bgx::detect_modules,
+    supervisor::{Backoff, LinkSupervisor},
+};
+
+fn main() -> Result<()> {
+    SimpleLogger::new().init().unwrap();
+
+    if let Some(mut bgx) = detect_modules().unwrap().pop() {
+        bgx.reach_well_known_state()?;
+
+        let supervisor = LinkSupervisor::start(
+            bgx,
+            "d0cf5e828506".parse()?,
+            Backoff::default(),
+            Duration::from_secs(5),
+            |s| debug!("Link state: {:?}", s),
+        );
+
+        sleep(Duration::from_secs(60));
+
+        supervisor.stop()?;
+
+        Ok(())
+    } else {
+        Err(anyhow::anyhow!("Couldn't apply settings"))
+    }
+}
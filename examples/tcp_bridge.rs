@@ -0,0 +1,18 @@
+use anyhow::Result;
+use simple_logger::SimpleLogger;
+use std::{net::TcpListener, time::Duration};
+use BGX13P_lib_rust::bgx::detect_modules;
+
+fn main() -> Result<()> {
+    SimpleLogger::new().init().unwrap();
+
+    if let Some(mut bgx) = detect_modules().unwrap().pop() {
+        bgx.reach_well_known_state()?;
+
+        // e.g. `nc localhost 7000` talks to the peer afterwards
+        let listener = TcpListener::bind("127.0.0.1:7000")?;
+        bgx.serve_tcp(&listener, &"d0cf5e828506".parse()?, Duration::from_secs(5))
+    } else {
+        Err(anyhow::anyhow!("Couldn't find a BGX module"))
+    }
+}
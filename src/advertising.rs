@@ -0,0 +1,69 @@
+use anyhow::{anyhow, Result};
+
+/// advertising modes supported by the `adv` command
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AdvertisingMode {
+    /// fast advertising, switches to low after the high duration has elapsed
+    High,
+    /// slow advertising, stops after the low duration has elapsed
+    Low,
+}
+
+/// advertising timing of the module while acting as peripheral
+///
+/// Durations are given in seconds where 0 means advertising forever.
+/// Intervals are given in units of 0.625 ms as expected by the module.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AdvertisingSettings {
+    pub high_duration: u32,
+    pub high_interval: u16,
+    pub low_duration: u32,
+    pub low_interval: u16,
+}
+
+impl AdvertisingSettings {
+    /// range of advertising intervals accepted by the module (20 ms up to 10.24 s)
+    pub const INTERVAL_RANGE: std::ops::RangeInclusive<u16> = 32..=16384;
+
+    pub(crate) fn validate(&self) -> Result<()> {
+        for interval in [self.high_interval, self.low_interval] {
+            if !Self::INTERVAL_RANGE.contains(&interval) {
+                return Err(anyhow!(
+                    "Advertising interval {interval} is outside of {:?}",
+                    Self::INTERVAL_RANGE
+                ));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Default for AdvertisingSettings {
+    /// module defaults except that high advertising never times out
+    fn default() -> Self {
+        Self {
+            high_duration: 0,
+            high_interval: 32,
+            low_duration: 0,
+            low_interval: 1600,
+        }
+    }
+}
+
+#[test]
+fn advertising_settings_validate_1() {
+    assert!(AdvertisingSettings::default().validate().is_ok());
+
+    let s = AdvertisingSettings {
+        high_interval: 16,
+        ..Default::default()
+    };
+    assert!(s.validate().is_err());
+
+    let s = AdvertisingSettings {
+        low_interval: 20000,
+        ..Default::default()
+    };
+    assert!(s.validate().is_err());
+}
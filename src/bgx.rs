@@ -1,90 +1,215 @@
-use anyhow::{anyhow, Result};
-use log::{debug, info, trace};
-use serialport::{DataBits, FlowControl, Parity, SerialPort, SerialPortType, StopBits};
+use anyhow::{anyhow, Context, Result};
+use log::{debug, info, trace, warn};
+use serialport::{ClearBuffer, DataBits, FlowControl, Parity, SerialPort, StopBits};
 use std::{
-    io::{Read, Write},
+    collections::VecDeque,
+    io::{ErrorKind, Read, Write},
     thread::sleep,
-    time::Duration,
+    time::{Duration, Instant},
 };
-use tap::Tap;
 use winnow::FinishIResult;
 
 use crate::{
-    command::Command,
+    advertising::{AdvertisingMode, AdvertisingSettings},
+    command::{BgxCommand, Command, ConParams, InvalidCommand, ScanResults, TypedCommand},
+    con_param::ConInfo,
+    device_info::DeviceInfo,
+    discovery::{detect_modules_with_report, DiscoveryConfig},
     fw::parse_fw_ver,
+    human::{detect_protocol_mode, parse_human_response},
     mac::Mac,
-    response::{parse_response, BgxResponse, ResponseCodes},
+    raw::RawResponseError,
+    response::{find_header, parse_next_response, BgxResponse, ResponseCodes},
+    response_header::ResponseHeader,
     scan::ScanResult,
+    settings::{Profile, SettingFailure, SettingsError},
+    state::{ModuleState, ProtocolMode},
+    timeouts::{ConnectOptions, Timeouts},
 };
 
-/// searches and returns serial port devices connected via USB
+/// searches and returns BGX modules connected via USB adapters with the default VID/PID pairs
 pub fn detect_modules() -> Result<Vec<Bgx13p>> {
-    let ports = serialport::available_ports()?;
-    trace!("Detected the following ports: {:#?}", &ports);
+    let modules = detect_modules_with_report(&DiscoveryConfig::default())?.accepted;
 
-    let ports = ports
-        .into_iter()
-        .filter_map(|p| {
-            if let SerialPortType::UsbPort(n) = &p.port_type {
-                debug!("Found USB port: {:#?}", &n);
-
-                if n.manufacturer
-                    .as_ref()
-                    .map(|m| {
-                        m.contains("Silicon Labs") || m.contains("Cygnal") || m.contains("CP21")
-                    })
-                    .unwrap_or(false)
-                {
-                    return Some(p.port_name);
-                }
-            }
+    debug!("Detected modules: {:#?}", &modules);
 
-            None
-        })
-        .filter_map(|p| match Bgx13p::new(&p) {
-            Ok(m) => Some(m),
-            Err(e) => {
-                info!("USB device not used as BGX due to: {}", e);
-                None
-            }
-        })
-        .collect::<Vec<_>>()
-        .tap_mut(|v| {
-            // try to add a default best guess USB connection if detection of BGX fails
-            if v.is_empty() {
-                if let Ok(bgx) = Bgx13p::new("/dev/ttyUSB0") {
-                    v.push(bgx);
-                }
-            }
-        });
+    Ok(modules)
+}
+
+/// unsolicited data kept for read_responses at most, older data is dropped beyond it
+const MAX_UNSOLICITED_LEN: usize = 64 * 1024;
 
-    Ok(ports)
+/// name the module advertises after the default settings have been applied
+const DEFAULT_DEVICE_NAME: &str = "JugglerBGX";
+
+/// settings of the well known state, older FW doesn't know the PHY multiplexing
+fn default_profile(expect_old_fw: bool) -> Profile {
+    let mut variables = vec![
+        ("sy r e", "0"),
+        ("bl v h d", "0"),
+        ("bl e p", "any"),
+        ("bl p m", "0"),
+        ("bl p p", "1m"),
+        ("sy d n", DEFAULT_DEVICE_NAME),
+    ];
+    if expect_old_fw {
+        variables.retain(|(variable, _)| *variable != "bl p m");
+    }
+
+    Profile(
+        variables
+            .into_iter()
+            .map(|(variable, value)| (variable.to_string(), value.to_string()))
+            .collect(),
+    )
 }
 
+/// UART baud rates supported by the module, the default rate comes first as it is probed first
+pub const SUPPORTED_BAUD_RATES: [u32; 8] = [
+    Bgx13p::DEFAULT_BAUD_RATE,
+    9600,
+    19200,
+    38400,
+    57600,
+    230400,
+    460800,
+    921600,
+];
+
 pub struct Bgx13p {
-    port: Box<dyn SerialPort>,
-    default_settings_applied: bool,
+    pub(crate) port: Box<dyn SerialPort>,
+    pub(crate) default_settings_applied: bool,
+    hardware_flow_control: bool,
+    pub(crate) state: ModuleState,
+    pub(crate) device_info: Option<DeviceInfo>,
+    // received but not yet parsed, e.g. the start of a response which is still arriving
+    rx_buffer: Vec<u8>,
+    // data which has been received in front of responses, see read_responses
+    unsolicited: VecDeque<Vec<u8>>,
+    protocol_mode: Option<ProtocolMode>,
+    last_command: Vec<u8>,
+    pub(crate) timeouts: Timeouts,
+    // deadline of the running call, e.g. of connect_with, which all retries have to respect
+    deadline: Option<Instant>,
+    // the module falls asleep on its own after this long without UART activity, see set_power_settings
+    pub(crate) idle_timeout: Option<Duration>,
+    pub(crate) last_activity: Instant,
+    // started with start_advertising, a central may connect unnoticed in the meantime
+    pub(crate) advertising: bool,
+}
+
+impl std::fmt::Debug for Bgx13p {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Bgx13p")
+            .field("port", &self.port.name())
+            .field("state", &self.state)
+            .field("device_info", &self.device_info)
+            .finish()
+    }
 }
 
 impl Bgx13p {
-    fn new(port_name: &str) -> Result<Self> {
-        let op = serialport::new(port_name, 115200)
+    pub const DEFAULT_BAUD_RATE: u32 = 115200;
+
+    /**
+        Opens the port, probes the baud rate of the module and follows its flow control.
+        Nothing is written to the settings of the module, the host keeps the rate the module answers with,
+        see [`Bgx13p::set_baud_rate`] to bring it back to [`Bgx13p::DEFAULT_BAUD_RATE`].
+    */
+    pub fn open(port_name: &str) -> Result<Self> {
+        Self::open_with_port(Self::open_port(port_name)?)
+    }
+
+    /// uses an already opened port, e.g. a wrapped one, probes the baud rate of the module and follows its flow control
+    pub fn open_with_port(port: Box<dyn SerialPort>) -> Result<Self> {
+        let mut bgx = Self::new(port);
+        bgx.detect_uart_settings()?;
+
+        Ok(bgx)
+    }
+
+    /// like [`Bgx13p::open`] but probing the module has to finish before the deadline, nothing is written afterwards
+    pub(crate) fn open_until(port_name: &str, deadline: Instant) -> Result<Self> {
+        let mut bgx = Self::new(Self::open_port(port_name)?);
+        bgx.with_deadline(deadline, Self::detect_uart_settings)?;
+
+        Ok(bgx)
+    }
+
+    pub(crate) fn open_port(port_name: &str) -> Result<Box<dyn SerialPort>> {
+        Ok(serialport::new(port_name, Self::DEFAULT_BAUD_RATE)
             .data_bits(DataBits::Eight)
             .flow_control(FlowControl::None)
             .parity(Parity::None)
             .stop_bits(StopBits::One)
             .timeout(Command::TIMEOUT_COMMON)
-            .open()?;
+            .open()?)
+    }
 
-        Ok(Self {
-            port: op,
+    fn new(port: Box<dyn SerialPort>) -> Self {
+        Self {
+            port,
             default_settings_applied: Default::default(),
-        })
+            hardware_flow_control: Default::default(),
+            state: Default::default(),
+            device_info: Default::default(),
+            rx_buffer: Default::default(),
+            unsolicited: Default::default(),
+            protocol_mode: Default::default(),
+            last_command: Default::default(),
+            timeouts: Default::default(),
+            deadline: Default::default(),
+            idle_timeout: Default::default(),
+            last_activity: Instant::now(),
+            advertising: Default::default(),
+        }
+    }
+
+    /// state of the module as tracked by the host
+    pub fn state(&self) -> ModuleState {
+        self.state
+    }
+
+    /// mode of the command answers, detected when the FW version is read, None if not known yet
+    pub fn protocol_mode(&self) -> Option<ProtocolMode> {
+        self.protocol_mode
+    }
+
+    /// switches between machine and human mode without saving it, answers are parsed accordingly afterwards
+    pub fn set_protocol_mode(&mut self, mode: ProtocolMode) -> Result<()> {
+        self.switch_to_command_mode()?;
+
+        // the answer may already come in the new mode, so only the status line is checked
+        self.write_command(&BgxCommand::SetProtocolMode(mode), None)?;
+        let acknowledged = |b: &[u8]| b.windows(9).any(|w| w == b"Success\r\n");
+        let mut answer = Vec::new();
+        self.read_until(
+            Instant::now() + self.timeouts.settings,
+            &mut answer,
+            acknowledged,
+        )?;
+        let answer_str = String::from_utf8_lossy(&answer);
+        trace!("{mode:?} mode answer: {:?}", answer_str);
+
+        if !acknowledged(&answer) {
+            return Err(anyhow!(
+                "Couldn't activate {mode:?} mode, got: {answer_str:?}"
+            ));
+        }
+
+        self.protocol_mode = Some(mode);
+        if mode == ProtocolMode::Human {
+            // the well known state relies on machine mode
+            self.default_settings_applied = false;
+        }
+
+        Ok(())
     }
 
     /**
         Try to reach a well known state in which settings for further usage are set.
         This will also bring the module into the Command Mode and check for a compatible FW version.
+        Only settings which differ are written and saved, bondings are kept, see [`Bgx13p::clear_bondings`].
     */
     // this function is written in a way that it doesn't validate the protocol headers
     // as the module might not yet be configured to utilize these headers
@@ -96,24 +221,9 @@ impl Bgx13p {
 
         self.switch_to_command_mode()?;
 
-        // first try to request the FW version within a certain timeout
-        self.write_line(Command::GetVersion, None)?;
-
-        let answer: Vec<u8> = self
-            .port
-            .as_mut()
-            .bytes()
-            .take_while(|f| f.is_ok())
-            .collect::<Result<_, _>>()?;
-
-        let answer = std::str::from_utf8(&answer)?;
-        trace!("FW version feedback: {}", answer);
-
         // parse FW version and check if compatible
         // atm only BGX13P.1.2.2738 with multiple endings as ".2-1524-2738" is considered
-        let (_, fw_version) = parse_fw_ver(answer)
-            .finish_err()
-            .map_err(|e| e.into_owned())?;
+        let fw_version = self.read_fw_version()?;
         info!("Found FW string: {fw_version}");
         let other_fw = !fw_version.contains("BGX13P.1.2.2738");
 
@@ -126,7 +236,11 @@ impl Bgx13p {
         if let BgxResponse::DataWithHeader(n, _) = answer {
             if ResponseCodes::Success == n.response_code {
                 self.default_settings_applied = true;
-                info!("Reached well known state");
+
+                match self.device_info() {
+                    Ok(i) => info!("Reached well known state with {i}"),
+                    Err(e) => info!("Reached well known state, device info unavailable: {e}"),
+                }
 
                 return Ok(());
             }
@@ -135,32 +249,254 @@ impl Bgx13p {
         Err(anyhow::anyhow!("Couldn't reach a well known state"))
     }
 
-    /// Scans for nearby BGX modules.
+    /// FW version string of the module, e.g. BGX13P.1.2.2738.2-1524-2738
+    pub fn fw_version(&mut self) -> Result<String> {
+        self.switch_to_command_mode()?;
+
+        self.read_fw_version()
+    }
+
+    /// requests the FW version within a certain timeout, works with and without headers
+    pub(crate) fn read_fw_version(&mut self) -> Result<String> {
+        self.write_command(&BgxCommand::Version, None)?;
+
+        let answer = self.read_until_timeout()?;
+        // the version is answered in both modes, so this is where the mode is detected
+        self.protocol_mode = Some(detect_protocol_mode(&answer));
+
+        let answer = std::str::from_utf8(&answer)?;
+        trace!("FW version feedback: {}", answer);
+
+        let (_, fw_version) = parse_fw_ver(answer)
+            .finish_err()
+            .map_err(|e| e.into_owned())?;
+
+        Ok(fw_version.to_string())
+    }
+
+    /**
+        Changes the UART baud rate of the module, saves it and follows with the host port.
+        The new rate is verified by requesting the FW version, FW which applies the rate only after a reboot is rebooted.
+        If the module doesn't answer with the new rate, the host port follows the rate the module still answers with.
+    */
+    pub fn set_baud_rate(&mut self, rate: u32) -> Result<()> {
+        let cmd = BgxCommand::SetUartBaudRate(rate);
+        // unsupported rates are rejected before anything is sent
+        cmd.to_bytes()?;
+        let old_rate = self.port.baud_rate()?;
+
+        self.switch_to_command_mode()?;
+        let state = self.state;
+
+        self.write_expect_success(&cmd, self.timeouts.settings)?;
+        self.write_expect_success(&BgxCommand::Save, self.timeouts.settings)?;
+
+        let verified = self.verify_baud_rate(rate, state).or_else(|e| {
+            debug!("No answer with {rate} baud: {e}");
+
+            // the saved rate becomes active with the reboot
+            self.verify_baud_rate(old_rate, state)?;
+            info!("Reboot to apply {rate} baud");
+            self.clear_received()?;
+            self.write_command(&BgxCommand::Reboot, None)?;
+            self.set_host_baud_rate(rate)?;
+            self.wait_for_boot()?;
+
+            self.verify_baud_rate(rate, ModuleState::Command)
+        });
+
+        if let Err(e) = verified {
+            let active = self
+                .find_baud_rate()
+                .with_context(|| format!("Module doesn't answer with {rate} baud: {e}"))?;
+
+            return Err(e.context(format!(
+                "Module doesn't answer with {rate} baud, it still uses {active} baud"
+            )));
+        }
+
+        Ok(())
+    }
+
+    /// switches the host port to the rate and checks that the module answers in the state it has been in
+    fn verify_baud_rate(&mut self, rate: u32, state: ModuleState) -> Result<()> {
+        self.set_host_baud_rate(rate)?;
+        // the module stays in command mode, so it answers right away or not at all
+        self.probe_command_mode(0)?;
+        self.state = state;
+
+        let fw_version = self.read_fw_version()?;
+        info!("Module {fw_version} answers with {rate} baud");
+
+        Ok(())
+    }
+
+    /// probes the baud rate and lets the host port follow the flow control of the module
+    fn detect_uart_settings(&mut self) -> Result<()> {
+        self.find_baud_rate()?;
+
+        let enabled = match self.get_variable(Command::VariableFlowControl) {
+            Ok(v) => v.eq_ignore_ascii_case("on"),
+            Err(e) => {
+                warn!("Couldn't read flow control of the module, keep it disabled: {e}");
+                return Ok(());
+            }
+        };
+        if enabled {
+            self.set_host_flow_control(true)?;
+
+            if !self.port.read_clear_to_send()? {
+                warn!(
+                    "Module uses flow control but CTS isn't asserted, keep it disabled on the host"
+                );
+                self.set_host_flow_control(false)?;
+            }
+        }
+        debug!("Host follows flow control {enabled} of the module");
+
+        Ok(())
+    }
+
+    /// probes all supported baud rates until the module answers, the host port keeps that rate
+    /// longest time the detection of the UART settings takes, with the module answering at the last rate
+    pub(crate) fn max_detect_duration(timeouts: &Timeouts) -> Duration {
+        let per_rate = Command::GUARD_TIME_BREAK * 2 + timeouts.common * 4;
+
+        // the version, the flow control and the connection are read at the answering rate
+        per_rate * SUPPORTED_BAUD_RATES.len() as u32 + timeouts.settings * 3
+    }
+
+    fn find_baud_rate(&mut self) -> Result<u32> {
+        for rate in SUPPORTED_BAUD_RATES {
+            self.check_deadline(Duration::ZERO)?;
+
+            debug!("Probe module with {rate} baud");
+            self.set_host_baud_rate(rate)?;
+
+            // a single break per rate, a module at the last rate would take too long otherwise
+            match self
+                .switch_to_command_mode_with(Command::RETRIES_BAUD_RATE_SWEEP)
+                .and_then(|_| self.read_fw_version())
+            {
+                Ok(fw_version) => {
+                    info!("Module {fw_version} found with {rate} baud");
+                    return Ok(rate);
+                }
+                Err(e) => debug!("No module answered with {rate} baud: {e}"),
+            }
+        }
+
+        Err(anyhow!("Module didn't answer with any supported baud rate"))
+    }
+
+    /// drops everything received so far, e.g. before a reboot so that the boot banner isn't lost afterwards
+    pub(crate) fn clear_received(&mut self) -> Result<()> {
+        self.port.clear(ClearBuffer::All)?;
+        self.rx_buffer.clear();
+        self.unsolicited.clear();
+
+        Ok(())
+    }
+
+    pub(crate) fn set_host_baud_rate(&mut self, rate: u32) -> Result<()> {
+        // pending output would be sent with the new rate otherwise
+        self.port.flush()?;
+        self.port.set_baud_rate(rate)?;
+        // anything parsed with the old rate is meaningless, the port keeps what arrived meanwhile, e.g. a boot banner
+        self.rx_buffer.clear();
+        self.unsolicited.clear();
+        self.state = ModuleState::Unknown;
+
+        Ok(())
+    }
+
+    /**
+        Enables or disables RTS/CTS flow control on the module and the host port together, the module setting is saved.
+        When enabling, the CTS line of the host is checked to detect adapters without connected flow control lines.
+    */
+    pub fn set_hardware_flow_control(&mut self, enabled: bool) -> Result<()> {
+        self.switch_to_command_mode()?;
+
+        self.write_expect_success(
+            &BgxCommand::SetUartFlowControl(enabled),
+            self.timeouts.settings,
+        )?;
+        self.write_expect_success(&BgxCommand::Save, self.timeouts.settings)?;
+
+        self.set_host_flow_control(enabled)?;
+
+        // the module asserts its RTS which is our CTS as soon as it is able to receive
+        if enabled && !self.port.read_clear_to_send()? {
+            warn!("CTS not asserted by module, fall back to no flow control");
+
+            self.set_host_flow_control(false)?;
+            self.write_expect_success(
+                &BgxCommand::SetUartFlowControl(false),
+                self.timeouts.settings,
+            )?;
+            self.write_expect_success(&BgxCommand::Save, self.timeouts.settings)?;
+
+            return Err(anyhow!(
+                "CTS line isn't asserted, check whether RTS/CTS of the adapter are connected"
+            ));
+        }
+
+        let fw_version = self.read_fw_version()?;
+        info!("Module {fw_version} answers with flow control {enabled}");
+
+        Ok(())
+    }
+
+    /// switches RTS/CTS flow control of the host port only
+    pub(crate) fn set_host_flow_control(&mut self, enabled: bool) -> Result<()> {
+        self.port.set_flow_control(if enabled {
+            FlowControl::Hardware
+        } else {
+            FlowControl::None
+        })?;
+        self.hardware_flow_control = enabled;
+
+        Ok(())
+    }
+
+    /// Scans for nearby BGX modules for the scan timeout, see [`Timeouts::scan`].
     /// Module must not be connect or scan will fail.
     pub fn scan(&mut self) -> Result<ScanResult> {
         debug!("BGX starts scanning for devices...");
 
-        self.switch_to_command_mode()?;
         self.disconnect()?;
-        self.write_line(Command::SCAN, None)?;
+        // only wakes the module up if it is asleep as disconnect already left the stream mode
+        self.switch_to_command_mode()?;
+        // the results wouldn't be read in time otherwise
+        self.check_deadline(self.timeouts.scan)?;
+        self.write_command(&BgxCommand::Scan, None)?;
         self.read_bgx_response(None)?;
-        sleep(Duration::from_secs(10));
-        self.write_line(Command::SCAN_RESULTS, None)?;
-        let ans = self.read_bgx_response(None)?;
+        self.state = ModuleState::Scanning;
+        sleep(self.timeouts.scan);
+        self.write_command(&BgxCommand::ScanResults, None)?;
+        let ans = self.read_bgx_response(None);
+        // the module answers commands again once the results are read
+        self.state = match ans {
+            Ok(_) => ModuleState::Command,
+            Err(_) => ModuleState::Unknown,
+        };
+        let ans = ans?;
 
-        let res = ans.try_into()?;
+        let res = ScanResults.parse(ans)?;
         debug!("BGX finished scanning for devices");
 
         Ok(res)
     }
 
     /// writes a command to the module which ends with \r\n and errors on timeout
-    fn write_line(
+    pub(crate) fn write_line(
         &mut self,
         cmd: &[u8],
         custom_timeout: impl Into<Option<Duration>>,
     ) -> Result<()> {
         let command = [cmd, Command::LINEBREAK].concat();
+        // human mode echoes the command in front of the answer
+        self.last_command = cmd.to_vec();
 
         self.write_all_with_timeout(&command, custom_timeout)
     }
@@ -184,239 +520,1194 @@ impl Bgx13p {
         payload: &[u8],
         timeout: impl Into<Option<Duration>>,
     ) -> Result<()> {
-        self.port
-            .set_timeout(timeout.into().unwrap_or(Command::TIMEOUT_COMMON))?;
+        // with hardware flow control the module pauses the transfer on its own,
+        // the timeout then only detects a stalled module
+        self.check_deadline(Duration::ZERO)?;
+
+        let timeout = timeout.into().unwrap_or(if self.hardware_flow_control {
+            self.timeouts.flow_control_stall
+        } else {
+            self.timeouts.common
+        });
+        self.port.set_timeout(timeout)?;
 
         self.port.write_all(payload)?;
+        self.last_activity = Instant::now();
 
         Ok(())
     }
 
-    /// reads any BGX response but does not validate whether the response code is and error response
-    fn read_bgx_response(&mut self, timeout: impl Into<Option<Duration>>) -> Result<BgxResponse> {
+    /**
+        Reads any BGX response but does not validate whether the response code is and error response.
+        Data in front of a response is kept as unsolicited data, the response is returned.
+    */
+    pub(crate) fn read_bgx_response(
+        &mut self,
+        timeout: impl Into<Option<Duration>>,
+    ) -> Result<BgxResponse> {
+        if self.answers_in_human_mode() {
+            self.port
+                .set_timeout(timeout.into().unwrap_or(self.timeouts.common))?;
+            let answer = self.read_until_timeout()?;
+
+            return Ok(if answer.is_empty() {
+                BgxResponse::DataWithoutHeader(answer)
+            } else {
+                let (h, ans) = parse_human_response(&answer, &self.last_command);
+                BgxResponse::DataWithHeader(h, ans)
+            });
+        }
+
+        if let Some(resp) = self.next_buffered_response(false) {
+            return Ok(resp);
+        }
+
         self.port
-            .set_timeout(timeout.into().unwrap_or(Command::TIMEOUT_COMMON))?;
+            .set_timeout(timeout.into().unwrap_or(self.timeouts.common))?;
+        self.receive_until_timeout()?;
+
+        Ok(self
+            .next_buffered_response(true)
+            .unwrap_or(BgxResponse::DataWithoutHeader(Vec::new())))
+    }
 
-        let bytes: Vec<u8> = self
-            .port
-            .as_mut()
-            .bytes()
-            .take_while(|f| f.is_ok())
-            .collect::<Result<_, _>>()?;
+    /**
+        Reads until nothing more arrives within the timeout and returns all responses and data in the order they arrived.
+        Data which has been received in front of earlier responses comes first, incomplete responses are kept for the next read.
+    */
+    pub fn read_responses(&mut self, timeout: Duration) -> Result<Vec<BgxResponse>> {
+        self.port.set_timeout(timeout)?;
+        self.receive_until_timeout()?;
 
-        let (_, resp) = parse_response(&bytes)
-            .finish_err()
-            .map_err(|e| anyhow!("BGX response error: {e:?}"))?;
+        let mut responses = self
+            .unsolicited
+            .drain(..)
+            .map(BgxResponse::DataWithoutHeader)
+            .collect::<Vec<_>>();
+        while let Some(resp) = self.next_buffered(true) {
+            responses.push(resp);
+        }
 
-        Ok(resp)
+        Ok(responses)
     }
 
-    /// resets the module to factory default and applies default settings
-    fn apply_default_settings(&mut self, expect_old_fw: bool) -> Result<()> {
-        self.switch_to_command_mode()?;
+    /// takes the next complete response or data off the receive buffer
+    fn next_buffered(&mut self, at_end: bool) -> Option<BgxResponse> {
+        let (resp, consumed) = parse_next_response(&self.rx_buffer, at_end)?;
+        self.rx_buffer.drain(..consumed);
 
-        let cmds: Vec<&[u8]> = if expect_old_fw {
-            vec![
-                Command::SetModuleToMachineMode,
-                Command::SystemRemoteCommandingFalse,
-                Command::AdvertiseHighDuration,
-                Command::BLEEncryptionPairingAny,
-                Command::BLEPHYPreference1M,
-                Command::SetDeviceName,
-                Command::ClearAllBondings,
-                Command::Save,
-            ]
-        } else {
-            vec![
-                Command::SetModuleToMachineMode,
-                Command::SystemRemoteCommandingFalse,
-                Command::AdvertiseHighDuration,
-                Command::BLEEncryptionPairingAny,
-                Command::BLEPHYMultiplexFalse,
-                Command::BLEPHYPreference1M,
-                Command::SetDeviceName,
-                Command::ClearAllBondings,
-                Command::Save,
-            ]
+        // headers are only sent in command mode, so the module has left the stream mode on its own
+        if matches!(resp, BgxResponse::DataWithHeader(..))
+            && matches!(self.state, ModuleState::Stream | ModuleState::Connected(_))
+        {
+            debug!("Module left stream mode, got {resp:?}");
+            self.state = ModuleState::Unknown;
+        }
+
+        Some(resp)
+    }
+
+    /// like next_buffered but data directly in front of a response is put aside and the response is returned
+    fn next_buffered_response(&mut self, at_end: bool) -> Option<BgxResponse> {
+        match self.next_buffered(at_end)? {
+            BgxResponse::DataWithoutHeader(d)
+                if matches!(
+                    parse_next_response(&self.rx_buffer, at_end),
+                    Some((BgxResponse::DataWithHeader(..), _))
+                ) =>
+            {
+                warn!("Data before header: {:?}", d);
+                self.put_aside(d);
+                self.next_buffered(at_end)
+            }
+            resp => Some(resp),
+        }
+    }
+
+    /// keeps data for read_responses, the oldest data is dropped if nobody reads it
+    pub(crate) fn put_aside(&mut self, data: Vec<u8>) {
+        if data.is_empty() {
+            return;
+        }
+        self.unsolicited.push_back(data);
+
+        let mut len = self.unsolicited.iter().map(Vec::len).sum::<usize>();
+        while len > MAX_UNSOLICITED_LEN {
+            if let Some(dropped) = self.unsolicited.pop_front() {
+                warn!(
+                    "Drop {} bytes of unsolicited data nobody read",
+                    dropped.len()
+                );
+                len -= dropped.len();
+            }
+        }
+    }
+
+    /// takes all data which has been put aside in the order it arrived
+    pub(crate) fn take_unsolicited(&mut self) -> Vec<u8> {
+        self.unsolicited.drain(..).flatten().collect()
+    }
+
+    /// whether answers have to be parsed as human mode, stream data is never framed
+    fn answers_in_human_mode(&self) -> bool {
+        self.protocol_mode == Some(ProtocolMode::Human)
+            && !matches!(
+                self.state,
+                ModuleState::Stream | ModuleState::Connected(_) | ModuleState::Remote(_)
+            )
+    }
+
+    /// reads from the port until nothing more arrives within the currently set timeout and takes everything received
+    fn read_until_timeout(&mut self) -> Result<Vec<u8>> {
+        self.receive_until_timeout()?;
+
+        Ok(std::mem::take(&mut self.rx_buffer))
+    }
+
+    /// reads into the receive buffer until nothing more arrives within the currently set timeout
+    fn receive_until_timeout(&mut self) -> Result<()> {
+        while self.receive()? > 0 {}
+
+        Ok(())
+    }
+
+    /// reads once into the receive buffer, returns the number of bytes received or 0 after a timeout
+    fn receive(&mut self) -> Result<usize> {
+        let mut buf = [0u8; 256];
+
+        match self.port.read(&mut buf) {
+            Ok(n) => {
+                self.rx_buffer
+                    .extend_from_slice(buf.get(..n).unwrap_or_default());
+                self.last_activity = Instant::now();
+                Ok(n)
+            }
+            Err(e) if e.kind() == ErrorKind::TimedOut => Ok(0),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// reads until a complete response with header arrived, returns as soon as it is complete
+    pub(crate) fn read_complete_response(
+        &mut self,
+        timeout: Duration,
+    ) -> Result<(ResponseHeader, String)> {
+        let (h, payload) = self.read_complete_raw_response(timeout)?;
+
+        let answer = match String::from_utf8(payload) {
+            Ok(answer) => answer,
+            Err(e) => format!("{:?}", e.as_bytes()),
         };
 
+        Ok((h, answer))
+    }
+
+    /// like read_complete_response but the payload is returned as received
+    pub(crate) fn read_complete_raw_response(
+        &mut self,
+        timeout: Duration,
+    ) -> Result<(ResponseHeader, Vec<u8>)> {
+        let deadline = Instant::now() + timeout;
+        self.port.set_timeout(self.timeouts.common)?;
+
+        if self.answers_in_human_mode() {
+            // without a header the answer is complete as soon as nothing more arrives
+            while self.receive()? != 0 || self.rx_buffer.is_empty() {
+                if Instant::now() >= deadline {
+                    return Err(self.incomplete_response(timeout).into());
+                }
+            }
+
+            let answer = std::mem::take(&mut self.rx_buffer);
+            let (h, ans) = parse_human_response(&answer, &self.last_command);
+            return Ok((h, ans.into_bytes()));
+        }
+
+        loop {
+            if let Some((start, h, body)) = find_header(&self.rx_buffer) {
+                let len = usize::try_from(h.data_length)?;
+
+                if let Some(payload) = body.get(..len) {
+                    let payload = payload.to_vec();
+                    let consumed = self.rx_buffer.len() - body.len() + len;
+                    let mut before = self.rx_buffer.drain(..consumed).collect::<Vec<_>>();
+                    before.truncate(start);
+
+                    if !before.is_empty() {
+                        warn!("Data before header: {:?}", before);
+                        self.put_aside(before);
+                    }
+
+                    return Ok((h, payload));
+                }
+            }
+
+            if Instant::now() >= deadline {
+                return Err(self.incomplete_response(timeout).into());
+            }
+            self.receive()?;
+        }
+    }
+
+    /// takes what has been received so far for the error
+    fn incomplete_response(&mut self, timeout: Duration) -> RawResponseError {
+        RawResponseError::Incomplete {
+            timeout,
+            raw: std::mem::take(&mut self.rx_buffer),
+        }
+    }
+
+    /// takes the receive buffer and reads into bytes until the condition is met or the deadline has passed
+    pub(crate) fn read_until(
+        &mut self,
+        deadline: Instant,
+        bytes: &mut Vec<u8>,
+        done: impl Fn(&[u8]) -> bool,
+    ) -> Result<()> {
+        self.port.set_timeout(self.timeouts.common)?;
+        bytes.append(&mut self.rx_buffer);
+
+        while !done(bytes) && Instant::now() < deadline {
+            self.receive()?;
+            bytes.append(&mut self.rx_buffer);
+        }
+
+        Ok(())
+    }
+
+    /**
+        Applies the default settings, only variables which differ are written and saved.
+        Switching to machine mode is saved along, so it's only written once as well.
+    */
+    fn apply_default_settings(&mut self, expect_old_fw: bool) -> Result<()> {
+        let switched_mode = self.protocol_mode != Some(ProtocolMode::Machine);
+        if switched_mode {
+            self.set_protocol_mode(ProtocolMode::Machine)?;
+        }
+
+        let changes = self.apply_profile_changes(&default_profile(expect_old_fw))?;
+        if changes.0.is_empty() && switched_mode {
+            self.save()?;
+        }
+
+        Ok(())
+    }
+
+    /// sends each command on its own, waits for its acknowledgement and saves if all of them succeeded
+    pub(crate) fn apply_acknowledged(&mut self, cmds: &[BgxCommand]) -> Result<()> {
+        let mut failures = Vec::new();
         for cmd in cmds {
-            // longer timeout as the "save" command may take longer
-            self.write_line(cmd, Command::TIMEOUT_SETTINGS)?;
-            sleep(Duration::from_millis(200)); // here we do not use a read answer as it use rad until timeout and we do not know whether the header is already activated
-            info!("Successfully applied setting");
-        }
-
-        let bytes: Vec<u8> = self
-            .port
-            .as_mut()
-            .bytes()
-            .take_while(|f| f.is_ok())
-            .collect::<Result<_, _>>()?;
-
-        let answer = std::str::from_utf8(&bytes)?;
-        trace!("Applied settings read: {}", answer);
-
-        let count_success = answer.lines().filter(|l| l == &"Success").count();
-        let expected_success = if expect_old_fw { 8 } else { 9 };
-
-        if count_success != expected_success {
-            return Err(anyhow::anyhow!(
-                "Only got {} times success instead of expected {} times",
-                count_success,
-                expected_success
-            ));
+            self.write_command(cmd, None)?;
+            let (h, _) = self.read_complete_response(self.timeouts.settings)?;
+            let setting = cmd.to_string();
+
+            if h.response_code == ResponseCodes::Success {
+                debug!("Successfully applied setting {setting:?}");
+            } else {
+                warn!("Setting {setting:?} failed with {}", h.response_code);
+                failures.push(SettingFailure {
+                    setting,
+                    code: h.response_code,
+                });
+            }
         }
 
+        if !failures.is_empty() {
+            return Err(SettingsError::Rejected(failures).into());
+        }
+
+        // the "save" command may take longer, reading returns as soon as it is acknowledged
+        self.write_command(&BgxCommand::Save, None)?;
+        let (h, _) = self.read_complete_response(self.timeouts.settings)?;
+        if h.response_code != ResponseCodes::Success {
+            return Err(anyhow::Error::new(h.response_code).context("Couldn't save settings"));
+        }
+
+        info!("Successfully applied settings");
+
         Ok(())
     }
 
-    /// makes sure the module is not in stream mode anymore, should be ran before any other control commands should be send to the module
-    fn switch_to_command_mode(&mut self) -> Result<()> {
-        debug!("Check if in stream mode...");
-        self.port.set_timeout(Command::TIMEOUT_COMMON)?;
+    /**
+        Makes sure the module is not in stream mode anymore, should be ran before any other control commands should be send to the module.
+        Leaving the stream mode doesn't close a connection, so it's checked afterwards which peer is still linked.
+    */
+    pub(crate) fn switch_to_command_mode(&mut self) -> Result<()> {
+        self.switch_to_command_mode_with(Command::RETRIES_COMMAND_MODE)
+    }
 
-        // clear buffer to make sure what come later is nothing historical
-        let _ = self
-            .port
-            .as_mut()
-            .bytes()
-            .take_while(|f| f.is_ok())
-            .collect::<Result<Vec<_>, _>>()?;
+    /// like [`Bgx13p::switch_to_command_mode`] with the number of break sequences to try
+    fn switch_to_command_mode_with(&mut self, retries: u8) -> Result<()> {
+        // a connecting central puts the module into stream mode, so it's checked again after a pause
+        if self.state == ModuleState::Command
+            && self.advertising
+            && self.last_activity.elapsed() >= Command::INTERVAL_ADVERTISING_CHECK
+        {
+            debug!("A central may have connected meanwhile");
+            self.state = ModuleState::Stream;
+        }
 
-        // here we write two times and then read
-        // because we might have left over $$$ from an earlier command which hasn't been used as the device has not been in stream mode
-        self.write_line(b"", None)?;
-        self.write_line(b"", None)?;
+        let peer = match self.state {
+            // without a connection the module may have fallen asleep in the meantime
+            ModuleState::Command if self.idle_expired() => return self.wake(),
+            // nothing to do, this avoids sending anything into a live stream
+            ModuleState::Command | ModuleState::Scanning | ModuleState::Linked(_) => return Ok(()),
+            ModuleState::Asleep => return self.wake(),
+            ModuleState::Connected(mac) | ModuleState::Remote(mac) => Some(mac),
+            ModuleState::Stream | ModuleState::Unknown => None,
+        };
 
-        let read_from_port: Vec<u8> = self
-            .port
-            .as_mut()
-            .bytes()
-            .take_while(|f| f.is_ok())
-            .collect::<Result<_, _>>()?;
+        let mut broke = self.state != ModuleState::Unknown;
+        if broke {
+            debug!("Leave stream mode...");
+            self.state = ModuleState::Unknown;
+            self.send_break_sequence()?;
+        }
 
-        if !read_from_port.is_empty() {
-            trace!("Got one or more Ready --> not in stream mode");
+        broke |= self.probe_command_mode(retries)?;
 
-            // do not use common read answer method here as we can not always relying on getting a header due to a module being configured properly
-            let answer = std::str::from_utf8(&read_from_port)?;
-            trace!("Read from port test: {:?}", answer);
+        self.state = match peer {
+            Some(mac) => ModuleState::Linked(mac),
+            // stream mode without a known peer, the connection may still be open
+            None if broke => match self.con_info()? {
+                Some(ConInfo(mac)) => ModuleState::Linked(mac),
+                None => ModuleState::Command,
+            },
+            None => ModuleState::Command,
+        };
 
-            return Ok(());
-        } else {
-            debug!("No answer, expect stream mode, try to leave...");
-            sleep(Duration::from_millis(550));
-            self.port.write_all(Command::BreakSequence)?;
-            sleep(Duration::from_millis(550)); // min. 500 ms silence on UART for breakout sequence
+        Ok(())
+    }
+
+    /// whether the module was idle long enough to fall asleep on its own
+    fn idle_expired(&self) -> bool {
+        self.idle_timeout
+            .is_some_and(|timeout| self.last_activity.elapsed() >= timeout)
+    }
+
+    /// checks whether the module answers commands and tries to leave stream mode otherwise, returns whether a break was needed
+    fn probe_command_mode(&mut self, retries: u8) -> Result<bool> {
+        for retry in 0..=retries {
+            debug!("Check if in stream mode...");
+            self.port.set_timeout(self.timeouts.common)?;
+
+            // clear buffer to make sure what come later is nothing historical
+            let _ = self.read_until_timeout()?;
+
+            // here we write two times and then read
+            // because we might have left over $$$ from an earlier command which hasn't been used as the device has not been in stream mode
+            self.write_line(b"", None)?;
+            self.write_line(b"", None)?;
+
+            let read_from_port = self.read_until_timeout()?;
+
+            if !read_from_port.is_empty() {
+                trace!("Got one or more Ready --> not in stream mode");
+
+                // do not use common read answer method here as we can not always relying on getting a header due to a module being configured properly
+                let answer = std::str::from_utf8(&read_from_port)?;
+                trace!("Read from port test: {:?}", answer);
+
+                if retry > 0 {
+                    debug!("Stream mode left");
+                }
+
+                return Ok(retry > 0);
+            }
+
+            if retry == retries {
+                break;
+            }
 
-            let _ = self
-                .port
-                .as_mut()
-                .bytes()
-                .take_while(|f| f.is_ok())
-                .collect::<Result<Vec<_>, _>>()?;
+            debug!("No answer, expect stream mode, try to leave...");
+            self.send_break_sequence()?;
 
             debug!("Recheck if in stream mode...");
-            self.switch_to_command_mode()?;
         }
 
-        debug!("Stream mode left");
+        Err(anyhow!("Module doesn't answer, couldn't leave stream mode"))
+    }
+
+    /// data received around the break is still forwarded by the module, so it's kept for read_responses
+    fn send_break_sequence(&mut self) -> Result<()> {
+        // the break sequence needs its guard times before and after
+        self.check_deadline(Command::GUARD_TIME_BREAK * 2)?;
+
+        sleep(Command::GUARD_TIME_BREAK);
+        self.port.write_all(Command::BreakSequence)?;
+        self.last_activity = Instant::now();
+        sleep(Command::GUARD_TIME_BREAK);
+
+        self.port.set_timeout(self.timeouts.common)?;
+        let data = self.read_until_timeout()?;
+        self.put_aside(data);
 
         Ok(())
     }
 
+    /// checks without writing whether the module left the stream mode of its connection, e.g. because the link dropped
+    pub(crate) fn left_stream_mode(&mut self) -> Result<bool> {
+        if let ModuleState::Connected(_) = self.state {
+            self.port.set_timeout(self.timeouts.common)?;
+            self.receive()?;
+
+            // received data stays buffered for the next read
+            if find_header(&self.rx_buffer).is_some() {
+                debug!("Module left stream mode on its own");
+                self.state = ModuleState::Unknown;
+            }
+        }
+
+        Ok(!matches!(self.state, ModuleState::Connected(_)))
+    }
+
+    /// runs the call with a deadline which all retries and writes have to respect
+    pub(crate) fn with_deadline<T>(
+        &mut self,
+        deadline: Instant,
+        call: impl FnOnce(&mut Self) -> Result<T>,
+    ) -> Result<T> {
+        self.deadline = Some(deadline);
+        let res = call(self);
+        self.deadline = None;
+
+        res
+    }
+
+    /// errors if the deadline of the running call passes within the time the next step needs
+    fn check_deadline(&self, needed: Duration) -> Result<()> {
+        match self.deadline {
+            Some(deadline) if Instant::now() + needed > deadline => {
+                Err(anyhow!("Deadline of the call passed"))
+            }
+            _ => Ok(()),
+        }
+    }
+
     /// connects to a device with a given mac,
     /// skips if already connected to the device and disconnects before connecting to a new device
     pub fn connect(&mut self, mac: &Mac) -> Result<()> {
-        self.switch_to_command_mode()?;
+        self.try_connect(mac, true)
+    }
 
-        self.disconnect()?;
+    /**
+        Like [`Bgx13p::connect`] but the whole call has to finish within the timeout of the options,
+        including leaving the stream mode with all retries and disconnecting an old link.
+        The module tries to connect as long as time is left.
+    */
+    pub fn connect_with(&mut self, mac: &Mac, options: ConnectOptions) -> Result<()> {
+        self.with_deadline(Instant::now() + options.timeout, |bgx| {
+            bgx.try_connect(mac, options.clear_bondings_on_mismatch)
+        })
+    }
 
-        self.write_line(&Command::Connect(mac), Command::TIMEOUT_CONNECT)?;
-        let ans = self.read_bgx_response(Command::TIMEOUT_CONNECT)?;
+    fn try_connect(&mut self, mac: &Mac, clear_bondings_on_mismatch: bool) -> Result<()> {
+        match self.state {
+            ModuleState::Connected(peer) if peer == *mac => {
+                debug!("Already connected to {mac}");
+                return Ok(());
+            }
+            ModuleState::Linked(peer) if peer == *mac => {
+                debug!("Still connected to {mac}, return to stream mode");
+                return self.enter_stream_mode(*mac);
+            }
+            _ => {}
+        }
+
+        self.disconnect()?;
+        // only wakes the module up if it is asleep as disconnect already left the stream mode
+        self.switch_to_command_mode()?;
 
-        match ans {
-            BgxResponse::DataWithHeader(h, _) => match h.response_code {
-                ResponseCodes::CommandFailed => {
-                    self.disconnect()?;
-                    Err(anyhow::anyhow!("Command failed as devices where still connected but now has been disconnected"))
+        let (timeout_secs, timeout) = match self.deadline {
+            None => (
+                self.timeouts.connect_secs(),
+                Duration::from_secs(self.timeouts.connect_secs()) + self.timeouts.settings,
+            ),
+            Some(deadline) => {
+                let left = deadline.saturating_duration_since(Instant::now());
+                // the module has to give up early enough to report it before the deadline
+                match left.saturating_sub(self.timeouts.settings).as_secs() {
+                    0 => return Err(anyhow!("No time left to connect to {mac}")),
+                    secs => (secs, left),
                 }
-                ResponseCodes::SecurityMismatch => {
-                    self.write_line(Command::ClearAllBondings, None)?;
-                    if let Ok(BgxResponse::DataWithHeader(h, _)) = self.read_bgx_response(None) {
-                        if h.response_code == ResponseCodes::Success {
-                            return Err(anyhow::anyhow!(
-                                "Security mismatch but performed clear bonding on device"
-                            ));
-                        }
-                    }
+            }
+        };
+        self.write_command(
+            &BgxCommand::Connect {
+                mac: *mac,
+                timeout_secs,
+            },
+            None,
+        )?;
+
+        let h = match self.read_complete_response(timeout) {
+            Ok((h, _)) => h,
+            Err(e) => {
+                self.state = ModuleState::Unknown;
+                return Err(e.context("No answer when being in connection process"));
+            }
+        };
 
-                    Err(anyhow::anyhow!(
+        match h.response_code {
+            ResponseCodes::CommandFailed => {
+                // connection state is unclear, let disconnect check it
+                self.state = ModuleState::Unknown;
+                self.disconnect()?;
+                Err(anyhow::anyhow!(
+                    "Command failed as devices where still connected but now has been disconnected"
+                ))
+            }
+            ResponseCodes::SecurityMismatch if clear_bondings_on_mismatch => {
+                match self.write_expect_success(&BgxCommand::ClearBondings, None) {
+                    Ok(()) => Err(anyhow::anyhow!(
+                        "Security mismatch but performed clear bonding on device"
+                    )),
+                    Err(_) => Err(anyhow::anyhow!(
                         "Security mismatch and performing clear bonding didn't worked out"
-                    ))
+                    )),
                 }
-                ResponseCodes::Success => Ok(()),
-                ResponseCodes::Timeout => Err(anyhow::anyhow!(
-                    "Couldn't connect to device within given time."
-                )),
-                _ => Err(anyhow::anyhow!(
-                    "Error when handling connection but no plan how to handle it: {:?}",
-                    h
-                )),
-            },
-            BgxResponse::DataWithoutHeader(_) => Err(anyhow::anyhow!(
-                "Got data without header when being in connection process"
+            }
+            ResponseCodes::SecurityMismatch => Err(anyhow::anyhow!(
+                "Security mismatch, bondings have to be cleared to connect"
+            )),
+            ResponseCodes::Success => {
+                self.state = ModuleState::Connected(*mac);
+                Ok(())
+            }
+            ResponseCodes::Timeout => Err(anyhow::anyhow!(
+                "Couldn't connect to device within given time."
+            )),
+            _ => Err(anyhow::anyhow!(
+                "Error when handling connection but no plan how to handle it: {:?}",
+                h
             )),
         }
     }
 
+    /// disconnects an active connection, a sleeping module is not woken up
     pub fn disconnect(&mut self) -> Result<()> {
+        let known = match self.state {
+            ModuleState::Command | ModuleState::Scanning | ModuleState::Asleep => {
+                debug!("BGX not connected, no disconnect necessary");
+                return Ok(());
+            }
+            ModuleState::Unknown => false,
+            ModuleState::Stream
+            | ModuleState::Connected(_)
+            | ModuleState::Remote(_)
+            | ModuleState::Linked(_) => true,
+        };
+
         self.switch_to_command_mode()?;
 
-        // ConParams command is only available starting from BGX FW 1.2045
-        self.write_line(Command::ConParams, None)?;
-        let r = self.read_bgx_response(None)?;
-        match r {
-            BgxResponse::DataWithHeader(h, ans) => match h.response_code {
-                ResponseCodes::Success => {
-                    // sample output active connection
-                    /*
-                        R000108\r\n
-                        !  Param Value\r\n
-                        #  Addr  EC1BBD1B12A1\r\n
-                        #  Itvl  12\r\n
-                        #  Mtu   250\r\n
-                        #  Phy   1m\r\n
-                        #  Tout  400\r\n
-                        #  Err   023E\r\n
-                    */
-
-                    // sample output no active connection
-                    /*
-                        R000031\r\n
-                        !  Param Value\r\n
-                        #  Err   0208\r\n
-                    */
-                    if ans.contains("Addr") {
-                        self.write_line(Command::Disconnect, None)?;
-                        self.read_bgx_response(Command::TIMEOUT_DISCONNECT)?;
-
-                        return Ok(());
-                    }
+        let connected = match self.state {
+            ModuleState::Linked(_) => true,
+            // the connection is only checked when leaving the stream mode
+            _ if !known => self.con_info()?.is_some(),
+            _ => false,
+        };
+        if !connected {
+            debug!("BGX not connected, no disconnect necessary");
+            return Ok(());
+        }
+
+        self.write_command(&BgxCommand::Disconnect, None)?;
+        self.read_bgx_response(self.timeouts.disconnect)?;
+        self.state = ModuleState::Command;
+
+        Ok(())
+    }
+
+    /// removes all bondings, afterwards peers have to pair again
+    pub fn clear_bondings(&mut self) -> Result<()> {
+        self.switch_to_command_mode()?;
+
+        self.write_expect_success(&BgxCommand::ClearBondings, self.timeouts.settings)
+    }
+
+    /**
+        Starts advertising so that a central is able to connect to the module.
+        Until it's stopped, the host checks again for a connection before commands after a pause without traffic.
+    */
+    pub fn start_advertising(&mut self, mode: AdvertisingMode) -> Result<()> {
+        self.switch_to_command_mode()?;
+
+        // the module stays in command mode until a central connects, see wait_for_connection
+        self.write_expect_success(&BgxCommand::Advertise(mode), None)?;
+        self.advertising = true;
+
+        Ok(())
+    }
+
+    pub fn stop_advertising(&mut self) -> Result<()> {
+        self.switch_to_command_mode()?;
+
+        self.write_expect_success(&BgxCommand::AdvertiseOff, None)?;
+        self.advertising = false;
+
+        Ok(())
+    }
+
+    /// sets durations and intervals of high and low advertising, settings are not saved
+    pub fn set_advertising_settings(&mut self, settings: &AdvertisingSettings) -> Result<()> {
+        settings.validate()?;
+
+        self.switch_to_command_mode()?;
+
+        for cmd in [
+            BgxCommand::SetAdvertiseHighDuration(settings.high_duration),
+            BgxCommand::SetAdvertiseHighInterval(settings.high_interval),
+            BgxCommand::SetAdvertiseLowDuration(settings.low_duration),
+            BgxCommand::SetAdvertiseLowInterval(settings.low_interval),
+        ] {
+            self.write_expect_success(&cmd, self.timeouts.settings)?;
+        }
+
+        Ok(())
+    }
+
+    /**
+        Blocks until a central connected to the module while it acts as peripheral and returns the MAC of the central.
+        Afterwards the module is switched into stream mode.
+        Nothing is written while waiting as a connected central would receive it,
+        so the connection is detected by the first data of the central, events of the module are only logged.
+        Waits forever if no timeout is given.
+    */
+    pub fn wait_for_connection(&mut self, timeout: impl Into<Option<Duration>>) -> Result<Mac> {
+        let deadline = timeout.into().map(|t| Instant::now() + t);
+
+        self.switch_to_command_mode()?;
+
+        loop {
+            if let ModuleState::Linked(mac) = self.state {
+                info!("Central {mac} connected");
+
+                self.enter_stream_mode(mac)?;
+
+                return Ok(mac);
+            }
 
-                    debug!("BGX not connected, not disconnect necessary");
-                    Ok(())
+            self.port.set_timeout(self.timeouts.common)?;
+            while self.receive()? == 0 {
+                if deadline.is_some_and(|d| Instant::now() >= d) {
+                    return Err(anyhow!("No central connected within given time"));
                 }
-                _ => Err(anyhow::anyhow!(
-                    "Got error with header {:?} and content {:?}",
-                    h,
-                    ans
-                )),
-            },
-            BgxResponse::DataWithoutHeader(e) => {
-                Err(anyhow::anyhow!("Got data without header: {:?}", e))
             }
+
+            if find_header(&self.rx_buffer).is_some() {
+                // an event of the module which is still in command mode
+                let event = self.read_bgx_response(None)?;
+                debug!("Module reported {event:?} while waiting for a central");
+            } else {
+                // the module entered stream mode on the connection, this is data of the central
+                let data = std::mem::take(&mut self.rx_buffer);
+                self.put_aside(data);
+                self.state = ModuleState::Stream;
+                self.switch_to_command_mode()?;
+            }
+        }
+    }
+
+    /// switches back to stream mode of an existing connection, e.g. one established by another process
+    pub fn resume_stream(&mut self) -> Result<Mac> {
+        if let ModuleState::Connected(mac) = self.state {
+            return Ok(mac);
+        }
+
+        self.switch_to_command_mode()?;
+
+        let ConInfo(mac) = self
+            .con_info()?
+            .ok_or_else(|| anyhow!("No active connection to resume"))?;
+        self.enter_stream_mode(mac)?;
+
+        Ok(mac)
+    }
+
+    pub(crate) fn enter_stream_mode(&mut self, peer: Mac) -> Result<()> {
+        self.write_expect_success(&BgxCommand::StreamMode, None)?;
+        self.state = ModuleState::Connected(peer);
+
+        Ok(())
+    }
+
+    /// requests the connection parameters, returns None if there is no active connection
+    pub(crate) fn con_info(&mut self) -> Result<Option<ConInfo>> {
+        self.write_command(&BgxCommand::ConParams, None)?;
+        let info = ConParams.parse(self.read_bgx_response(None)?)?;
+
+        // the connection may have been opened or closed meanwhile
+        match (self.state, &info) {
+            (ModuleState::Linked(_), None) => self.state = ModuleState::Command,
+            (ModuleState::Command, Some(ConInfo(mac))) => self.state = ModuleState::Linked(*mac),
+            _ => {}
+        }
+
+        Ok(info)
+    }
+
+    /// reads a configuration variable, e.g. `sy d n`
+    pub(crate) fn get_variable(&mut self, variable: &str) -> Result<String> {
+        self.write_command(&BgxCommand::Get(variable.to_string()), None)?;
+
+        let (h, ans) = self.read_complete_response(self.timeouts.settings)?;
+        if h.response_code != ResponseCodes::Success {
+            return Err(anyhow::Error::new(h.response_code)
+                .context(format!("Couldn't get variable {variable:?}")));
+        }
+
+        Ok(ans.trim().to_string())
+    }
+
+    /**
+        Sends the command in command mode and parses its answer into the response type of the command.
+        A successful connect leads into stream mode, other commands which change the mode or the connection leave the state unknown,
+        so that it's checked again when needed.
+        Settings the host port has to follow are rejected, see [`InvalidCommand::HostSetting`].
+    */
+    pub fn execute<C: TypedCommand>(&mut self, cmd: C) -> Result<C::Response> {
+        let command = cmd.command();
+        // invalid arguments are rejected before the module is touched
+        if command.changes_host_settings() {
+            return Err(InvalidCommand::HostSetting(command.to_string()).into());
+        }
+        command.to_bytes()?;
+
+        self.switch_to_command_mode()?;
+        self.write_command(&command, None)?;
+        let res = self.read_complete_response(command.answer_timeout(&self.timeouts));
+        let succeeded = matches!(&res, Ok((h, _)) if h.response_code == ResponseCodes::Success);
+        self.track_command(Some(&command), succeeded);
+
+        let (h, ans) = res?;
+        cmd.parse(BgxResponse::DataWithHeader(h, ans))
+    }
+
+    /// updates the state after a command was answered, lines which aren't a known command may change anything
+    pub(crate) fn track_command(&mut self, command: Option<&BgxCommand>, succeeded: bool) {
+        match command {
+            Some(BgxCommand::Connect { mac, .. }) if succeeded => {
+                self.state = ModuleState::Connected(*mac);
+            }
+            Some(c) if !c.changes_state() => {}
+            _ => self.state = ModuleState::Unknown,
         }
     }
+
+    /// validates and writes a command, see [`Bgx13p::write_line`]
+    pub(crate) fn write_command(
+        &mut self,
+        cmd: &BgxCommand,
+        custom_timeout: impl Into<Option<Duration>>,
+    ) -> Result<()> {
+        self.write_line(&cmd.to_bytes()?, custom_timeout)
+    }
+
+    /// writes a command and errors if the module doesn't answer with a success header
+    pub(crate) fn write_expect_success(
+        &mut self,
+        cmd: &BgxCommand,
+        timeout: impl Into<Option<Duration>> + Copy,
+    ) -> Result<()> {
+        self.write_command(cmd, timeout)?;
+
+        match self.read_bgx_response(timeout)? {
+            BgxResponse::DataWithHeader(h, _) if h.response_code == ResponseCodes::Success => {
+                Ok(())
+            }
+            r => Err(anyhow!(
+                "Command {:?} failed with answer {:?}",
+                cmd.to_string(),
+                r
+            )),
+        }
+    }
+}
+
+#[test]
+fn read_responses_1() {
+    use crate::{recording::Traffic, response_header::ResponseHeader};
+
+    let mut bgx = crate::recording::replay_opened([
+        Traffic::Received(b"R000002\r\nokevent\r\nR000005\r\nerr".to_vec()),
+        Traffic::ReadTimeouts(1),
+        Traffic::Received(b"or".to_vec()),
+        Traffic::ReadTimeouts(1),
+    ]);
+    let header = |data_length| ResponseHeader {
+        response_code: ResponseCodes::Success,
+        data_length,
+    };
+
+    // the incomplete response is kept until the rest arrived
+    assert_eq!(
+        bgx.read_responses(Command::TIMEOUT_COMMON).unwrap(),
+        vec![
+            BgxResponse::DataWithHeader(header(2), "ok".to_string()),
+            BgxResponse::DataWithoutHeader(b"event\r\n".to_vec()),
+        ]
+    );
+    assert_eq!(
+        bgx.read_bgx_response(None).unwrap(),
+        BgxResponse::DataWithHeader(header(5), "error".to_string())
+    );
+}
+
+#[test]
+fn put_aside_1() {
+    let mut bgx = crate::recording::replay_opened([]);
+
+    bgx.put_aside(vec![1; MAX_UNSOLICITED_LEN]);
+    bgx.put_aside(Vec::new());
+    bgx.put_aside(vec![2; 3]);
+
+    // the oldest data is dropped as a whole
+    assert_eq!(
+        bgx.read_responses(Command::TIMEOUT_COMMON).unwrap(),
+        vec![BgxResponse::DataWithoutHeader(vec![2; 3])]
+    );
+}
+
+#[test]
+fn disconnect_1() {
+    use crate::recording::Traffic;
+
+    // the module is still in stream mode of an old connection when it's opened
+    let traffic = [
+        Traffic::Sent(b"\r\n\r\n".to_vec()),
+        Traffic::ReadTimeouts(2),
+        Traffic::Sent(b"$$$".to_vec()),
+        Traffic::ReadTimeouts(1),
+        Traffic::Sent(b"\r\n\r\n".to_vec()),
+        Traffic::Received(b"Ready\r\n".to_vec()),
+        Traffic::ReadTimeouts(1),
+        Traffic::Sent(b"con params\r\n".to_vec()),
+        Traffic::Received(b"R000039\r\n!  Param Value\r\n#  Addr  d0cf5e828506\r\n".to_vec()),
+        Traffic::ReadTimeouts(1),
+        Traffic::Sent(b"ver\r\n".to_vec()),
+        Traffic::Received(b"R000029\r\nBGX13P.1.2.2738.2-1524-2738\r\n".to_vec()),
+        Traffic::ReadTimeouts(1),
+        Traffic::Sent(b"get ua f\r\n".to_vec()),
+        Traffic::Received(b"R000005\r\noff\r\n".to_vec()),
+        Traffic::Sent(b"dct\r\n".to_vec()),
+        Traffic::Received(b"R000000\r\n".to_vec()),
+        Traffic::ReadTimeouts(1),
+    ];
+    let mut bgx = Bgx13p::open_with_port(crate::recording::replay(traffic)).unwrap();
+    let mac = "d0cf5e828506".parse().unwrap();
+    assert_eq!(bgx.state(), ModuleState::Linked(mac));
+
+    bgx.disconnect().unwrap();
+    assert_eq!(bgx.state(), ModuleState::Command);
+    // nothing else has been sent, e.g. a second check of the connection
+    assert!(bgx.write_all_with_timeout(b"con params\r\n", None).is_err());
+}
+
+#[test]
+fn wait_for_connection_1() {
+    use crate::recording::Traffic;
+
+    let mut bgx = crate::recording::replay_opened([
+        Traffic::Sent(b"adv high\r\n".to_vec()),
+        Traffic::Received(b"R000000\r\n".to_vec()),
+        Traffic::ReadTimeouts(3),
+        // events only lead to further reading
+        Traffic::Received(b"R000000\r\n".to_vec()),
+        Traffic::ReadTimeouts(2),
+        // a central connected and sends data right away
+        Traffic::Received(b"hello".to_vec()),
+        Traffic::ReadTimeouts(1),
+        Traffic::Sent(b"$$$".to_vec()),
+        Traffic::ReadTimeouts(1),
+        Traffic::Sent(b"\r\n\r\n".to_vec()),
+        Traffic::Received(b"Ready\r\n".to_vec()),
+        Traffic::ReadTimeouts(1),
+        Traffic::Sent(b"con params\r\n".to_vec()),
+        Traffic::Received(b"R000039\r\n!  Param Value\r\n#  Addr  d0cf5e828506\r\n".to_vec()),
+        Traffic::ReadTimeouts(1),
+        Traffic::Sent(b"str\r\n".to_vec()),
+        Traffic::Received(b"R000000\r\n".to_vec()),
+        Traffic::ReadTimeouts(1),
+    ]);
+    let mac = "d0cf5e828506".parse().unwrap();
+
+    bgx.start_advertising(AdvertisingMode::High).unwrap();
+    assert_eq!(bgx.state(), ModuleState::Command);
+    assert_eq!(bgx.wait_for_connection(None).unwrap(), mac);
+    assert_eq!(bgx.state(), ModuleState::Connected(mac));
+    // the data of the central isn't lost
+    assert_eq!(
+        bgx.read_responses(Command::TIMEOUT_COMMON).unwrap(),
+        vec![BgxResponse::DataWithoutHeader(b"hello".to_vec())]
+    );
+}
+
+#[test]
+fn advertising_check_1() {
+    use crate::recording::Traffic;
+
+    let mac = "d0cf5e828506".parse().unwrap();
+    let mut bgx = crate::recording::replay_opened([
+        Traffic::Sent(b"adv high\r\n".to_vec()),
+        Traffic::Received(b"R000000\r\n".to_vec()),
+        // a central connected meanwhile without sending anything
+        Traffic::ReadTimeouts(1),
+        Traffic::Sent(b"$$$".to_vec()),
+        Traffic::ReadTimeouts(1),
+        Traffic::Sent(b"\r\n\r\n".to_vec()),
+        Traffic::Received(b"Ready\r\n".to_vec()),
+        Traffic::ReadTimeouts(1),
+        Traffic::Sent(b"con params\r\n".to_vec()),
+        Traffic::Received(b"R000039\r\n!  Param Value\r\n#  Addr  d0cf5e828506\r\n".to_vec()),
+        Traffic::ReadTimeouts(1),
+        Traffic::Sent(b"get sy d n\r\n".to_vec()),
+        Traffic::Received(b"R000012\r\nJugglerBGX\r\n".to_vec()),
+    ]);
+
+    bgx.start_advertising(AdvertisingMode::High).unwrap();
+    bgx.last_activity -= Command::INTERVAL_ADVERTISING_CHECK;
+
+    assert_eq!(bgx.get("sy d n").unwrap(), "JugglerBGX");
+    assert_eq!(bgx.state(), ModuleState::Linked(mac));
+}
+
+#[test]
+fn scan_1() {
+    use crate::recording::Traffic;
+
+    let mut bgx = crate::recording::replay_opened([
+        Traffic::Sent(b"scan\r\n".to_vec()),
+        Traffic::Received(b"R000000\r\n".to_vec()),
+        Traffic::ReadTimeouts(1),
+        Traffic::Sent(b"scan results\r\n".to_vec()),
+        Traffic::Received(
+            b"R000079\r\n!  # RSSI BD_ADDR           Device Name\r\n#  1  -47 d0:cf:5e:82:85:06 LOR-8090\r\n"
+                .to_vec(),
+        ),
+    ]);
+    bgx.set_timeouts(Timeouts {
+        scan: Duration::from_millis(10),
+        ..Default::default()
+    });
+
+    // nothing is started if the results couldn't be read in time
+    let e = bgx
+        .with_deadline(Instant::now() + Duration::from_millis(5), Bgx13p::scan)
+        .unwrap_err();
+    assert_eq!(e.to_string(), "Deadline of the call passed");
+
+    let res = bgx.scan().unwrap();
+    assert_eq!(res.0.len(), 1);
+    assert_eq!(bgx.state(), ModuleState::Command);
+}
+
+#[test]
+fn left_stream_mode_1() {
+    use crate::recording::Traffic;
+
+    let mut bgx = crate::recording::replay_opened([
+        Traffic::Received(b"data".to_vec()),
+        Traffic::ReadTimeouts(1),
+        Traffic::Received(b"R000000\r\n".to_vec()),
+    ]);
+    bgx.state = ModuleState::Connected("d0cf5e828506".parse().unwrap());
+
+    assert!(!bgx.left_stream_mode().unwrap());
+    assert!(!bgx.left_stream_mode().unwrap());
+    assert!(bgx.left_stream_mode().unwrap());
+    assert_eq!(bgx.state(), ModuleState::Unknown);
+
+    // nothing has been written and the data is still there
+    assert_eq!(
+        bgx.read_responses(Command::TIMEOUT_COMMON).unwrap().first(),
+        Some(&BgxResponse::DataWithoutHeader(b"data".to_vec()))
+    );
+}
+
+#[test]
+fn find_baud_rate_1() {
+    // nothing is written once the deadline passed, the replay would fail on any write
+    let mut bgx = Bgx13p::new(crate::recording::replay([]));
+    let e = bgx
+        .with_deadline(Instant::now(), Bgx13p::find_baud_rate)
+        .unwrap_err();
+    assert_eq!(e.to_string(), "Deadline of the call passed");
+}
+
+#[test]
+fn find_baud_rate_2() {
+    use crate::{discovery::DiscoveryConfig, recording::Traffic};
+
+    // a single break per silent rate, the module answers at the last one
+    let mut traffic = Vec::new();
+    for _ in SUPPORTED_BAUD_RATES.iter().skip(1) {
+        traffic.push(Traffic::Sent(b"\r\n\r\n".to_vec()));
+        traffic.push(Traffic::Sent(b"$$$".to_vec()));
+        traffic.push(Traffic::Sent(b"\r\n\r\n".to_vec()));
+    }
+    traffic.extend([
+        Traffic::Sent(b"\r\n\r\n".to_vec()),
+        Traffic::Received(b"Ready\r\n".to_vec()),
+        Traffic::ReadTimeouts(1),
+        Traffic::Sent(b"ver\r\n".to_vec()),
+        Traffic::Received(b"R000029\r\nBGX13P.1.2.2738.2-1524-2738\r\n".to_vec()),
+        Traffic::ReadTimeouts(1),
+        Traffic::Sent(b"get ua f\r\n".to_vec()),
+        Traffic::Received(b"R000005\r\noff\r\n".to_vec()),
+    ]);
+
+    let mut bgx = Bgx13p::new(crate::recording::replay(traffic));
+    let deadline = Instant::now() + DiscoveryConfig::default().probe_budget;
+    bgx.with_deadline(deadline, Bgx13p::detect_uart_settings)
+        .unwrap();
+    assert_eq!(bgx.port.baud_rate().unwrap(), 921600);
+    assert_eq!(bgx.state(), ModuleState::Command);
+}
+
+#[test]
+fn detect_uart_settings_1() {
+    use crate::recording::Traffic;
+
+    let traffic = [
+        Traffic::Sent(b"\r\n\r\n".to_vec()),
+        Traffic::Received(b"Ready\r\n".to_vec()),
+        Traffic::ReadTimeouts(1),
+        Traffic::Sent(b"ver\r\n".to_vec()),
+        Traffic::Received(b"R000029\r\nBGX13P.1.2.2738.2-1524-2738\r\n".to_vec()),
+        Traffic::ReadTimeouts(1),
+        Traffic::Sent(b"get ua f\r\n".to_vec()),
+        Traffic::Received(b"R000004\r\non\r\n".to_vec()),
+    ];
+
+    let bgx = Bgx13p::open_with_port(crate::recording::replay(traffic)).unwrap();
+    assert!(bgx.hardware_flow_control);
+    assert_eq!(bgx.port.flow_control().unwrap(), FlowControl::Hardware);
+}
+
+/// traffic of changing the baud rate to 9600 up to the first verification
+#[cfg(test)]
+fn set_baud_rate_traffic(answers: bool) -> Vec<crate::recording::Traffic> {
+    use crate::recording::Traffic;
+
+    let mut traffic = vec![
+        Traffic::Sent(b"set ua b 9600\r\n".to_vec()),
+        Traffic::Received(b"R000000\r\n".to_vec()),
+        Traffic::ReadTimeouts(1),
+        Traffic::Sent(b"save\r\n".to_vec()),
+        Traffic::Received(b"R000000\r\n".to_vec()),
+        Traffic::ReadTimeouts(1),
+    ];
+    traffic.extend(verify_traffic(answers));
+
+    traffic
+}
+
+/// probe and FW version of a verification
+#[cfg(test)]
+fn verify_traffic(answers: bool) -> Vec<crate::recording::Traffic> {
+    use crate::recording::Traffic;
+
+    if !answers {
+        return vec![
+            Traffic::Sent(b"\r\n\r\n".to_vec()),
+            Traffic::ReadTimeouts(2),
+        ];
+    }
+
+    vec![
+        Traffic::Sent(b"\r\n\r\n".to_vec()),
+        Traffic::Received(b"Ready\r\n".to_vec()),
+        Traffic::ReadTimeouts(1),
+        Traffic::Sent(b"ver\r\n".to_vec()),
+        Traffic::Received(b"R000029\r\nBGX13P.1.2.2738.2-1524-2738\r\n".to_vec()),
+        Traffic::ReadTimeouts(1),
+    ]
+}
+
+#[test]
+fn set_baud_rate_1() {
+    let mut bgx = crate::recording::replay_opened(set_baud_rate_traffic(true));
+
+    bgx.set_baud_rate(9600).unwrap();
+    assert_eq!(bgx.port.baud_rate().unwrap(), 9600);
+    assert_eq!(bgx.state(), ModuleState::Command);
+}
+
+#[test]
+fn set_baud_rate_reboot_1() {
+    use crate::recording::Traffic;
+
+    // the module applies the rate only after a reboot
+    let traffic = set_baud_rate_traffic(false)
+        .into_iter()
+        .chain(verify_traffic(true))
+        .chain([
+            Traffic::Sent(b"reboot\r\n".to_vec()),
+            Traffic::Received(b"\r\nBGX13P.1.2.2738.2-1524-2738\r\n".to_vec()),
+            Traffic::ReadTimeouts(1),
+        ])
+        .chain(verify_traffic(true));
+    let mut bgx = crate::recording::replay_opened(traffic);
+
+    bgx.set_baud_rate(9600).unwrap();
+    assert_eq!(bgx.port.baud_rate().unwrap(), 9600);
+}
+
+#[test]
+fn set_baud_rate_fallback_1() {
+    use crate::recording::Traffic;
+
+    // the module doesn't take the rate even after a reboot
+    let traffic = set_baud_rate_traffic(false)
+        .into_iter()
+        .chain(verify_traffic(true))
+        .chain([
+            Traffic::Sent(b"reboot\r\n".to_vec()),
+            Traffic::Received(b"\r\nBGX13P.1.2.2738.2-1524-2738\r\n".to_vec()),
+            Traffic::ReadTimeouts(1),
+        ])
+        .chain(verify_traffic(false))
+        .chain(verify_traffic(true));
+    let mut bgx = crate::recording::replay_opened(traffic);
+
+    let e = bgx.set_baud_rate(9600).unwrap_err();
+    assert_eq!(
+        e.to_string(),
+        "Module doesn't answer with 9600 baud, it still uses 115200 baud"
+    );
+    assert_eq!(bgx.port.baud_rate().unwrap(), Bgx13p::DEFAULT_BAUD_RATE);
+}
+
+#[test]
+fn reach_well_known_state_1() {
+    use crate::recording::Traffic;
+
+    let exchange = |sent: &str, received: &str| {
+        [
+            Traffic::Sent(format!("{sent}\r\n").into_bytes()),
+            Traffic::Received(format!("R{:06}\r\n{received}", received.len()).into_bytes()),
+        ]
+    };
+    let traffic = [
+        exchange("ver", "BGX13P.1.2.2738.2-1524-2738\r\n"),
+        // the module is provisioned already
+        exchange("get sy r e", "0\r\n"),
+        exchange("get bl v h d", "0\r\n"),
+        exchange("get bl e p", "any\r\n"),
+        exchange("get bl p m", "0\r\n"),
+        exchange("get bl p p", "1M\r\n"),
+        exchange("get sy d n", "JugglerBGX\r\n"),
+        exchange("", ""),
+        // device info
+        exchange("ver", "BGX13P.1.2.2738.2-1524-2738\r\n"),
+        exchange("get bl a", "d0cf5e828506\r\n"),
+        exchange("get sy b v", "1.2.2738\r\n"),
+        exchange("get sy u", "0123\r\n"),
+    ];
+    let mut bgx = crate::recording::replay_opened(traffic.into_iter().flatten());
+
+    bgx.reach_well_known_state().unwrap();
+    assert!(bgx.device_info.is_some());
+    // neither settings nor save have been sent
+    assert!(bgx.write_all_with_timeout(b"save\r\n", None).is_err());
 }
@@ -0,0 +1,329 @@
+use anyhow::{Context, Result};
+use clap::{Parser, Subcommand};
+use log::LevelFilter;
+use serde_json::json;
+use simple_logger::SimpleLogger;
+use std::{
+    io::{stdout, Write},
+    net::TcpListener,
+    time::Duration,
+};
+#[cfg(target_os = "linux")]
+use BGX13P_lib_rust::pty_bridge::VirtualPort;
+use BGX13P_lib_rust::{
+    bgx::{detect_modules, Bgx13p},
+    discovery::{discover_modules, open_by_usb_serial, DiscoveryConfig},
+    mac::Mac,
+    settings::Profile,
+    timeouts::ConnectOptions,
+};
+
+/// Controls BGX13P modules connected via USB
+#[derive(Debug, Parser)]
+#[command(version)]
+struct Cli {
+    /// serial port of the module, the first detected module is used if neither port nor USB serial are given
+    #[arg(short, long, global = true)]
+    port: Option<String>,
+    /// serial number of the USB adapter of the module
+    #[arg(short, long, global = true, conflicts_with = "port")]
+    usb_serial: Option<String>,
+    /// records all traffic on the port into the given file
+    #[arg(long, global = true, requires = "port")]
+    record: Option<std::path::PathBuf>,
+    /// uses a recording instead of a module, the same command has to be run as when recording
+    #[arg(long, global = true, conflicts_with_all = ["port", "usb_serial"])]
+    replay: Option<std::path::PathBuf>,
+    /// increases the log level, may be given multiple times
+    #[arg(short, long, global = true, action = clap::ArgAction::Count)]
+    verbose: u8,
+    #[command(subcommand)]
+    command: Cmd,
+}
+
+#[derive(Debug, Subcommand)]
+enum Cmd {
+    /// lists the modules connected via USB
+    List {
+        #[arg(long)]
+        json: bool,
+    },
+    /// scans for BLE devices nearby
+    Scan {
+        #[arg(long)]
+        json: bool,
+    },
+    /// connects to the peer with the given MAC and leaves the link in stream mode
+    Connect {
+        mac: Mac,
+        /// deadline of the whole connection attempt in seconds
+        #[arg(short, long)]
+        timeout: Option<u64>,
+    },
+    /// closes the current link
+    Disconnect,
+    /// prints address, model and firmware of the module
+    Info {
+        #[arg(long)]
+        json: bool,
+    },
+    /// reads or writes configuration variables
+    Config {
+        #[command(subcommand)]
+        command: ConfigCmd,
+    },
+    /// sends data over the open link
+    Send {
+        data: String,
+        /// don't append a line break
+        #[arg(short, long)]
+        no_newline: bool,
+    },
+    /// prints data received over the open link
+    Recv {
+        /// how long to wait for data in milliseconds
+        #[arg(short, long, default_value_t = 1000)]
+        timeout: u64,
+    },
+    /// bridges TCP clients to the peer with the given MAC, one client at a time
+    TcpBridge {
+        mac: Mac,
+        #[arg(short, long, default_value = "127.0.0.1:7000")]
+        listen: String,
+        /// how long the link may be idle before it's checked, in milliseconds
+        #[arg(long, default_value_t = 5000)]
+        idle_check: u64,
+    },
+    /// creates a virtual serial port which is bridged to the peer with the given MAC while it's open
+    #[cfg(target_os = "linux")]
+    PtyBridge {
+        mac: Mac,
+        /// stable symlink to the pseudo-terminal
+        #[arg(short, long)]
+        link: Option<std::path::PathBuf>,
+        /// how long the link may be idle before it's checked, in milliseconds
+        #[arg(long, default_value_t = 5000)]
+        idle_check: u64,
+    },
+    /// interactive terminal, Ctrl-] toggles between command and stream mode
+    Term,
+    /// firmware of the module
+    Fw {
+        #[command(subcommand)]
+        command: FwCmd,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+enum ConfigCmd {
+    /// reads a variable, e.g. `sy d n`
+    Get { variable: String },
+    /// writes a variable, it's lost on reboot unless saved
+    Set {
+        variable: String,
+        value: String,
+        #[arg(long)]
+        save: bool,
+    },
+    /// writes, verifies and saves all variables of a profile file with `variable = value` lines,
+    /// the previous values are restored if anything fails
+    ApplyProfile {
+        file: std::path::PathBuf,
+        /// reboots the module and verifies the values again
+        #[arg(long)]
+        reboot: bool,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+enum FwCmd {
+    /// prints the firmware version
+    Version,
+}
+
+fn main() -> Result<()> {
+    let cli = Cli::parse();
+
+    SimpleLogger::new()
+        .with_level(match cli.verbose {
+            0 => LevelFilter::Warn,
+            1 => LevelFilter::Info,
+            2 => LevelFilter::Debug,
+            _ => LevelFilter::Trace,
+        })
+        .init()?;
+
+    if let Cmd::List { json } = cli.command {
+        return list(json);
+    }
+
+    let mut bgx = open_module(&cli)?;
+
+    match cli.command {
+        Cmd::List { .. } => unreachable!("handled without opening a module"),
+        Cmd::Scan { json } => {
+            bgx.reach_well_known_state()?;
+            let devices = bgx.scan()?.0;
+
+            if json {
+                let devices = devices
+                    .iter()
+                    .map(|d| json!({"mac": d.mac.to_string(), "name": d.friendly_name, "rssi": d.rssi}))
+                    .collect::<Vec<_>>();
+                println!("{}", serde_json::to_string_pretty(&devices)?);
+            } else {
+                println!("{:>5}  {:<12}  NAME", "RSSI", "MAC");
+                for d in devices {
+                    println!("{:>5}  {:<12}  {}", d.rssi, d.mac, d.friendly_name);
+                }
+            }
+        }
+        Cmd::Connect { mac, timeout } => {
+            bgx.reach_well_known_state()?;
+            match timeout {
+                Some(secs) => bgx.connect_with(
+                    &mac,
+                    ConnectOptions {
+                        timeout: Duration::from_secs(secs),
+                        ..Default::default()
+                    },
+                )?,
+                None => bgx.connect(&mac)?,
+            }
+            println!("Connected to {mac}");
+        }
+        Cmd::Disconnect => bgx.disconnect()?,
+        Cmd::Info { json } => {
+            let info = bgx.device_info()?;
+
+            if json {
+                let info = json!({
+                    "mac": info.mac.to_string(),
+                    "model": info.model,
+                    "firmware": info.firmware,
+                    "bootloader": info.bootloader,
+                    "uuid": info.uuid,
+                });
+                println!("{}", serde_json::to_string_pretty(&info)?);
+            } else {
+                println!("{info}");
+            }
+        }
+        Cmd::Config { command } => match command {
+            ConfigCmd::Get { variable } => println!("{}", bgx.get(&variable)?),
+            ConfigCmd::Set {
+                variable,
+                value,
+                save,
+            } => {
+                bgx.set(&variable, &value)?;
+                if save {
+                    bgx.save()?;
+                }
+            }
+            ConfigCmd::ApplyProfile { file, reboot } => {
+                let profile = std::fs::read_to_string(&file)
+                    .with_context(|| format!("Couldn't read profile {}", file.display()))?
+                    .parse::<Profile>()?;
+                bgx.apply_profile_verified(&profile, reboot)?;
+            }
+        },
+        Cmd::Send { data, no_newline } => {
+            bgx.resume_stream()?;
+
+            let mut payload = data.into_bytes();
+            if !no_newline {
+                payload.push(b'\n');
+            }
+            bgx.write_all_with_timeout(&payload, None)?;
+        }
+        Cmd::Recv { timeout } => {
+            bgx.resume_stream()?;
+
+            let data = bgx.read_all_with_timeout(Duration::from_millis(timeout))?;
+            stdout().write_all(&data)?;
+        }
+        Cmd::TcpBridge {
+            mac,
+            listen,
+            idle_check,
+        } => {
+            bgx.reach_well_known_state()?;
+            let listener = TcpListener::bind(&listen)
+                .with_context(|| format!("Couldn't listen on {listen}"))?;
+            bgx.serve_tcp(&listener, &mac, Duration::from_millis(idle_check))?;
+        }
+        #[cfg(target_os = "linux")]
+        Cmd::PtyBridge {
+            mac,
+            link,
+            idle_check,
+        } => {
+            bgx.reach_well_known_state()?;
+            let mut port = VirtualPort::create(link)?;
+            println!("Virtual port: {}", port.path().display());
+            bgx.serve_pty(&mut port, &mac, Duration::from_millis(idle_check))?;
+        }
+        Cmd::Term => bgx.interactive_terminal()?,
+        Cmd::Fw {
+            command: FwCmd::Version,
+        } => println!("{}", bgx.fw_version()?),
+    }
+
+    Ok(())
+}
+
+fn list(json: bool) -> Result<()> {
+    let report = discover_modules(&DiscoveryConfig::default())?;
+
+    if json {
+        let modules = report
+            .accepted
+            .iter()
+            .map(|m| {
+                json!({
+                    "port": m.port_name,
+                    "usb_serial": m.usb_serial,
+                    "product": m.product,
+                    "bus_location": m.bus_location,
+                    "firmware": m.firmware,
+                    "mac": m.mac.map(|m| m.to_string()),
+                })
+            })
+            .collect::<Vec<_>>();
+        println!("{}", serde_json::to_string_pretty(&modules)?);
+    } else {
+        for m in report.accepted {
+            println!(
+                "{}  serial={}  mac={}  {}",
+                m.port_name,
+                m.usb_serial.as_deref().unwrap_or("-"),
+                m.mac.map(|m| m.to_string()).unwrap_or_else(|| "-".into()),
+                m.firmware
+            );
+        }
+        for r in report.rejected {
+            eprintln!("{} rejected: {}", r.port_name, r.reason);
+        }
+    }
+
+    Ok(())
+}
+
+fn open_module(cli: &Cli) -> Result<Bgx13p> {
+    if let Some(recording) = &cli.replay {
+        return Bgx13p::open_replay(recording);
+    }
+
+    match (&cli.port, &cli.usb_serial) {
+        (Some(port), _) => match &cli.record {
+            Some(recording) => Bgx13p::open_recorded(port, recording),
+            None => Bgx13p::open(port),
+        },
+        (None, Some(serial)) => open_by_usb_serial(serial, &DiscoveryConfig::default()),
+        (None, None) => detect_modules()?
+            .into_iter()
+            .next()
+            .context("No BGX module found"),
+    }
+}
@@ -0,0 +1,155 @@
+use anyhow::Result;
+use log::{debug, warn};
+use std::{
+    io::{ErrorKind, Read, Write},
+    time::{Duration, Instant},
+};
+
+use crate::{bgx::Bgx13p, mac::Mac, response::BgxResponse, supervisor::link_alive};
+
+/// why forwarding between a client and the BLE link ended
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BridgeEnd {
+    /// client closed the connection, the link has been disconnected
+    ClientLeft,
+    /// BLE link dropped, the client is only told by closing its connection
+    LinkLost,
+}
+
+impl Bgx13p {
+    /**
+        Connects to the target and pipes bytes between the client and the link in stream mode.
+        Reads from the client have to return after a short timeout, e.g. with `WouldBlock`, any other error is taken as the client having left.
+        As checking the link needs the break sequence, it's only done after `idle_check` without traffic.
+        Nothing but data of the peer is written to the client, failures are logged and the caller closes the client connection.
+    */
+    pub fn bridge<C: Read + Write>(
+        &mut self,
+        client: &mut C,
+        target: &Mac,
+        idle_check: Duration,
+    ) -> Result<BridgeEnd> {
+        if let Err(e) = self.connect(target) {
+            warn!("Couldn't connect to {target}: {e:#}");
+            return Err(e);
+        }
+
+        let mut buf = [0u8; 256];
+        let mut last_traffic = Instant::now();
+
+        let end = loop {
+            match client.read(&mut buf) {
+                Ok(0) => break BridgeEnd::ClientLeft,
+                Ok(n) => {
+                    self.write_all_with_timeout(buf.get(..n).unwrap_or_default(), None)?;
+                    last_traffic = Instant::now();
+                }
+                Err(e) if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => {}
+                Err(e) => {
+                    debug!("Reading from TCP client failed: {e}");
+                    break BridgeEnd::ClientLeft;
+                }
+            }
+
+            let mut check_link = last_traffic.elapsed() >= idle_check;
+            match self.read_bgx_response(self.timeouts.common)? {
+                BgxResponse::DataWithoutHeader(d) if d.is_empty() => {}
+                BgxResponse::DataWithoutHeader(d) => {
+                    if let Err(e) = client.write_all(&d) {
+                        debug!("Writing to TCP client failed: {e}");
+                        break BridgeEnd::ClientLeft;
+                    }
+                    last_traffic = Instant::now();
+                }
+                BgxResponse::DataWithHeader(h, ans) => {
+                    // headers are only sent in command mode, so the module has left the stream mode on its own
+                    debug!("Unexpected response {h:?} in stream mode: {ans:?}");
+                    check_link = true;
+                }
+            }
+
+            if check_link {
+                // data in front of an event and data received up to the break belongs to the client
+                if !self.forward_unsolicited(client) {
+                    break BridgeEnd::ClientLeft;
+                }
+                let alive = link_alive(self, target)?;
+                if !self.forward_unsolicited(client) {
+                    break BridgeEnd::ClientLeft;
+                }
+
+                if !alive {
+                    break BridgeEnd::LinkLost;
+                }
+                last_traffic = Instant::now();
+            }
+        };
+
+        match end {
+            BridgeEnd::ClientLeft => self.disconnect()?,
+            BridgeEnd::LinkLost => warn!("Link to {target} lost"),
+        }
+
+        Ok(end)
+    }
+
+    /// writes the data which has been put aside to the client, returns false if the client left
+    fn forward_unsolicited<C: Write>(&mut self, client: &mut C) -> bool {
+        let data = self.take_unsolicited();
+        if data.is_empty() {
+            return true;
+        }
+
+        match client.write_all(&data) {
+            Ok(()) => true,
+            Err(e) => {
+                debug!("Writing to TCP client failed: {e}");
+                false
+            }
+        }
+    }
+}
+
+#[test]
+fn bridge_1() {
+    use crate::recording::Traffic;
+
+    /// client which never sends anything
+    struct Client(Vec<u8>);
+    impl Read for Client {
+        fn read(&mut self, _buf: &mut [u8]) -> std::io::Result<usize> {
+            Err(ErrorKind::WouldBlock.into())
+        }
+    }
+    impl Write for Client {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.write(buf)
+        }
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    let mut bgx = crate::recording::replay_opened([
+        Traffic::Sent(b"con d0cf5e828506 2\r\n".to_vec()),
+        Traffic::Received(b"R000000\r\n".to_vec()),
+        Traffic::ReadTimeouts(1),
+        // the link drops right after the peer sent data
+        Traffic::Received(b"dataR000000\r\n".to_vec()),
+        Traffic::ReadTimeouts(1),
+        Traffic::Sent(b"\r\n\r\n".to_vec()),
+        Traffic::Received(b"Ready\r\n".to_vec()),
+        Traffic::ReadTimeouts(1),
+        Traffic::Sent(b"con params\r\n".to_vec()),
+        Traffic::Received(b"R000031\r\n!  Param Value\r\n#  Err   0208\r\n".to_vec()),
+        Traffic::ReadTimeouts(1),
+    ]);
+    let mac = "d0cf5e828506".parse().unwrap();
+    let mut client = Client(Vec::new());
+
+    let end = bgx
+        .bridge(&mut client, &mac, Duration::from_secs(60))
+        .unwrap();
+    assert_eq!(end, BridgeEnd::LinkLost);
+    assert_eq!(client.0, b"data");
+}
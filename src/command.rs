@@ -1,52 +1,569 @@
-use std::time::Duration;
+use anyhow::{anyhow, Result};
+use std::{fmt::Display, str::FromStr, time::Duration};
+use thiserror::Error;
 
-use crate::mac::Mac;
+use crate::{
+    advertising::{AdvertisingMode, AdvertisingSettings},
+    bgx::SUPPORTED_BAUD_RATES,
+    con_param::ConInfo,
+    mac::Mac,
+    response::{BgxResponse, ResponseCodes},
+    scan::ScanResult,
+    state::ProtocolMode,
+    timeouts::Timeouts,
+};
 
+/// commands of the module, rendered to the line which is sent with [`BgxCommand::to_bytes`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BgxCommand {
+    Version,
+    /// connects to the peer, the module gives up after the timeout
+    Connect {
+        mac: Mac,
+        timeout_secs: u64,
+    },
+    Disconnect,
+    StreamMode,
+    /// switches into command mode of the connected peer
+    RemoteCommandMode,
+    Advertise(AdvertisingMode),
+    AdvertiseOff,
+    Scan,
+    ScanResults,
+    ConParams,
+    /// reads a configuration variable, e.g. `sy d n`
+    Get(String),
+    /// writes any configuration variable, see the typed variants below for common ones
+    Set {
+        variable: String,
+        value: String,
+    },
+    SetProtocolMode(ProtocolMode),
+    /// up to 16 characters
+    SetDeviceName(String),
+    SetRemoteCommanding(bool),
+    SetAdvertiseHighDuration(u32),
+    /// in units of 0.625 ms within [`AdvertisingSettings::INTERVAL_RANGE`]
+    SetAdvertiseHighInterval(u16),
+    SetAdvertiseLowDuration(u32),
+    /// in units of 0.625 ms within [`AdvertisingSettings::INTERVAL_RANGE`]
+    SetAdvertiseLowInterval(u16),
+    /// one of [`SUPPORTED_BAUD_RATES`]
+    SetUartBaudRate(u32),
+    SetUartFlowControl(bool),
+    SetIdleTimeout(u32),
+    Save,
+    ClearBondings,
+    Sleep,
+    Reboot,
+    /// the own address of the module has to be given as confirmation
+    FactoryReset(Mac),
+}
+
+/// arguments the module would reject or which would break the line protocol
+#[derive(Debug, PartialEq, Eq, Error)]
+pub enum InvalidCommand {
+    #[error("Device name {0:?} has to have 1 to {max} characters without spaces", max = BgxCommand::MAX_DEVICE_NAME_LEN)]
+    DeviceName(String),
+    #[error(
+        "Advertising interval {0} is outside of {:?}",
+        AdvertisingSettings::INTERVAL_RANGE
+    )]
+    AdvertisingInterval(u16),
+    #[error("Baud rate {0} is not supported by the module")]
+    BaudRate(u32),
+    #[error("Argument {0:?} must neither be empty nor contain line breaks")]
+    Argument(String),
+    /// the host port would have to follow, which only the dedicated methods of [`crate::bgx::Bgx13p`] do
+    #[error("{0:?} changes settings of the host side, use set_protocol_mode, set_baud_rate or set_hardware_flow_control")]
+    HostSetting(String),
+    #[error("{0:?} isn't a known command")]
+    Unknown(String),
+}
+
+/// variables the host has to follow, by their words
+const HOST_VARIABLES: [&[&str]; 3] = [&["sy", "c", "m"], &["ua", "b"], &["ua", "f"]];
+
+impl BgxCommand {
+    pub const MAX_DEVICE_NAME_LEN: usize = 16;
+
+    /// writes any configuration variable
+    pub fn set(variable: &str, value: &str) -> Self {
+        Self::Set {
+            variable: variable.to_string(),
+            value: value.to_string(),
+        }
+    }
+
+    /// checks the arguments and renders the command without line break
+    pub fn to_bytes(&self) -> Result<Vec<u8>, InvalidCommand> {
+        self.validate()?;
+
+        Ok(self.to_string().into_bytes())
+    }
+
+    /// how long the answer may take, reading returns as soon as it is complete
+    pub(crate) fn answer_timeout(&self, timeouts: &Timeouts) -> Duration {
+        match self {
+            Self::Connect { timeout_secs, .. } => {
+                Duration::from_secs(*timeout_secs) + timeouts.settings
+            }
+            Self::Reboot | Self::FactoryReset(_) => timeouts.boot,
+            Self::Disconnect => timeouts.disconnect,
+            _ => timeouts.settings,
+        }
+    }
+
+    /// whether the mode or the connection of the module isn't known anymore afterwards
+    pub(crate) fn changes_state(&self) -> bool {
+        matches!(
+            self,
+            Self::Connect { .. }
+                | Self::Disconnect
+                | Self::StreamMode
+                | Self::RemoteCommandMode
+                | Self::Advertise(_)
+                | Self::Scan
+                | Self::Sleep
+                | Self::Reboot
+                | Self::FactoryReset(_)
+        )
+    }
+
+    /// whether the host port has to follow the command, the protocol mode or the UART settings
+    pub(crate) fn changes_host_settings(&self) -> bool {
+        match self {
+            Self::SetProtocolMode(_) | Self::SetUartBaudRate(_) | Self::SetUartFlowControl(_) => {
+                true
+            }
+            Self::Set { variable, .. } => {
+                let words: Vec<_> = variable.split_whitespace().collect();
+                HOST_VARIABLES.iter().any(|v| words.starts_with(v))
+            }
+            _ => false,
+        }
+    }
+
+    fn validate(&self) -> Result<(), InvalidCommand> {
+        let argument = |a: &str| {
+            if a.is_empty() || a.contains(['\r', '\n']) {
+                Err(InvalidCommand::Argument(a.to_string()))
+            } else {
+                Ok(())
+            }
+        };
+
+        match self {
+            Self::Get(variable) => argument(variable),
+            // the typed variants are sent by the methods which let the host follow
+            Self::Set { variable, .. } if self.changes_host_settings() => {
+                Err(InvalidCommand::HostSetting(variable.clone()))
+            }
+            Self::Set { variable, value } => argument(variable).and_then(|_| argument(value)),
+            Self::SetDeviceName(name) => {
+                argument(name)?;
+                if name.chars().count() > Self::MAX_DEVICE_NAME_LEN
+                    || name.contains(char::is_whitespace)
+                {
+                    return Err(InvalidCommand::DeviceName(name.clone()));
+                }
+                Ok(())
+            }
+            Self::SetAdvertiseHighInterval(i) | Self::SetAdvertiseLowInterval(i)
+                if !AdvertisingSettings::INTERVAL_RANGE.contains(i) =>
+            {
+                Err(InvalidCommand::AdvertisingInterval(*i))
+            }
+            Self::SetUartBaudRate(rate) if !SUPPORTED_BAUD_RATES.contains(rate) => {
+                Err(InvalidCommand::BaudRate(*rate))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+impl Display for BgxCommand {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let flag = |b: &bool| u8::from(*b);
+
+        match self {
+            Self::Version => write!(f, "ver"),
+            Self::Connect { mac, timeout_secs } => write!(f, "con {mac} {timeout_secs}"),
+            Self::Disconnect => write!(f, "dct"),
+            Self::StreamMode => write!(f, "str"),
+            Self::RemoteCommandMode => write!(f, "rmt"),
+            Self::Advertise(AdvertisingMode::High) => write!(f, "adv high"),
+            Self::Advertise(AdvertisingMode::Low) => write!(f, "adv low"),
+            Self::AdvertiseOff => write!(f, "adv off"),
+            Self::Scan => write!(f, "scan"),
+            Self::ScanResults => write!(f, "scan results"),
+            Self::ConParams => write!(f, "con params"),
+            Self::Get(variable) => write!(f, "get {variable}"),
+            Self::Set { variable, value } => write!(f, "set {variable} {value}"),
+            Self::SetProtocolMode(ProtocolMode::Machine) => write!(f, "set sy c m machine"),
+            Self::SetProtocolMode(ProtocolMode::Human) => write!(f, "set sy c m human"),
+            Self::SetDeviceName(name) => write!(f, "set sy d n {name}"),
+            Self::SetRemoteCommanding(enabled) => write!(f, "set sy r e {}", flag(enabled)),
+            Self::SetAdvertiseHighDuration(secs) => write!(f, "set bl v h d {secs}"),
+            Self::SetAdvertiseHighInterval(interval) => write!(f, "set bl v h i {interval}"),
+            Self::SetAdvertiseLowDuration(secs) => write!(f, "set bl v l d {secs}"),
+            Self::SetAdvertiseLowInterval(interval) => write!(f, "set bl v l i {interval}"),
+            Self::SetUartBaudRate(rate) => write!(f, "set ua b {rate}"),
+            Self::SetUartFlowControl(enabled) => {
+                write!(f, "set ua f {}", if *enabled { "on" } else { "off" })
+            }
+            Self::SetIdleTimeout(secs) => write!(f, "set sy s t {secs}"),
+            Self::Save => write!(f, "save"),
+            Self::ClearBondings => write!(f, "clrb"),
+            Self::Sleep => write!(f, "sleep"),
+            Self::Reboot => write!(f, "reboot"),
+            Self::FactoryReset(own_address) => write!(f, "fac {own_address}"),
+        }
+    }
+}
+
+/// parses a command line as typed by a user, the last word of `set` is taken as value
+impl FromStr for BgxCommand {
+    type Err = InvalidCommand;
+
+    fn from_str(line: &str) -> Result<Self, Self::Err> {
+        let unknown = || InvalidCommand::Unknown(line.to_string());
+        let words: Vec<_> = line.split_whitespace().collect();
+
+        let cmd = match words.as_slice() {
+            ["ver"] => Self::Version,
+            ["con", "params"] => Self::ConParams,
+            ["con", mac] => Self::Connect {
+                mac: mac.parse().map_err(|_| unknown())?,
+                timeout_secs: Command::TIMEOUT_CONNECT_BGX_INTERN,
+            },
+            ["con", mac, secs] => Self::Connect {
+                mac: mac.parse().map_err(|_| unknown())?,
+                timeout_secs: secs.parse().map_err(|_| unknown())?,
+            },
+            ["dct"] => Self::Disconnect,
+            ["str"] => Self::StreamMode,
+            ["rmt"] => Self::RemoteCommandMode,
+            ["adv", "high"] => Self::Advertise(AdvertisingMode::High),
+            ["adv", "low"] => Self::Advertise(AdvertisingMode::Low),
+            ["adv", "off"] => Self::AdvertiseOff,
+            ["scan"] => Self::Scan,
+            ["scan", "results"] => Self::ScanResults,
+            ["get", variable @ ..] if !variable.is_empty() => Self::Get(variable.join(" ")),
+            ["set", variable @ .., value] if !variable.is_empty() => {
+                Self::set(&variable.join(" "), value)
+            }
+            ["save"] => Self::Save,
+            ["clrb"] => Self::ClearBondings,
+            ["sleep"] => Self::Sleep,
+            ["reboot"] => Self::Reboot,
+            ["fac", mac] => Self::FactoryReset(mac.parse().map_err(|_| unknown())?),
+            _ => return Err(unknown()),
+        };
+
+        Ok(cmd)
+    }
+}
+
+/**
+    Command with a typed answer, executed by [`crate::bgx::Bgx13p::execute`].
+    The answer is parsed like the `TryFrom<BgxResponse>` impls of the response types.
+*/
+pub trait TypedCommand {
+    type Response;
+
+    fn command(&self) -> BgxCommand;
+
+    fn parse(&self, response: BgxResponse) -> Result<Self::Response>;
+}
+
+/// any command answers with its data which is returned if the module reports success
+impl TypedCommand for BgxCommand {
+    type Response = String;
+
+    fn command(&self) -> BgxCommand {
+        self.clone()
+    }
+
+    fn parse(&self, response: BgxResponse) -> Result<String> {
+        success(self, response)
+    }
+}
+
+/// devices found by the last scan
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ScanResults;
+
+impl TypedCommand for ScanResults {
+    type Response = ScanResult;
+
+    fn command(&self) -> BgxCommand {
+        BgxCommand::ScanResults
+    }
+
+    fn parse(&self, response: BgxResponse) -> Result<ScanResult> {
+        response.try_into()
+    }
+}
+
+/// peer of the active connection, None if there is no connection
+// ConParams command is only available starting from BGX FW 1.2045
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ConParams;
+
+impl TypedCommand for ConParams {
+    type Response = Option<ConInfo>;
+
+    fn command(&self) -> BgxCommand {
+        BgxCommand::ConParams
+    }
+
+    fn parse(&self, response: BgxResponse) -> Result<Option<ConInfo>> {
+        // sample output active connection
+        /*
+            R000108\r\n
+            !  Param Value\r\n
+            #  Addr  EC1BBD1B12A1\r\n
+            #  Itvl  12\r\n
+            #  Mtu   250\r\n
+            #  Phy   1m\r\n
+            #  Tout  400\r\n
+            #  Err   023E\r\n
+        */
+
+        // sample output no active connection
+        /*
+            R000031\r\n
+            !  Param Value\r\n
+            #  Err   0208\r\n
+        */
+        match response {
+            BgxResponse::DataWithHeader(h, ans) if h.response_code == ResponseCodes::Success => {
+                if !ans.contains("Addr") {
+                    return Ok(None);
+                }
+
+                BgxResponse::DataWithHeader(h, ans).try_into().map(Some)
+            }
+            r => Err(anyhow!("Couldn't request connection parameters: {:?}", r)),
+        }
+    }
+}
+
+/// own BLE address of the module
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OwnAddress;
+
+impl TypedCommand for OwnAddress {
+    type Response = Mac;
+
+    fn command(&self) -> BgxCommand {
+        BgxCommand::Get(Command::VariableOwnAddress.to_string())
+    }
+
+    fn parse(&self, response: BgxResponse) -> Result<Mac> {
+        success(&self.command(), response)?.parse()
+    }
+}
+
+/// data of a success answer, the response code as error otherwise
+fn success(cmd: &BgxCommand, response: BgxResponse) -> Result<String> {
+    match response {
+        BgxResponse::DataWithHeader(h, ans) if h.response_code == ResponseCodes::Success => {
+            Ok(ans.trim().to_string())
+        }
+        BgxResponse::DataWithHeader(h, ans) => Err(anyhow::Error::new(h.response_code)
+            .context(format!("Command {:?} failed with {ans:?}", cmd.to_string()))),
+        BgxResponse::DataWithoutHeader(d) => Err(anyhow!(
+            "Command {:?} answered without header: {d:?}",
+            cmd.to_string()
+        )),
+    }
+}
+
+/// protocol constants and timing which aren't commands on their own
 pub(crate) struct Command;
 
 impl Command {
-    pub const GetVersion: &'static [u8; 3] = b"ver";
-    pub fn Connect(mac: &Mac) -> Vec<u8> {
-        format!("con {} {}", mac, Command::TIMEOUT_CONNECT_BGX_INTERN)
-            .as_bytes()
-            .to_vec()
-    }
-    pub const Disconnect: &'static [u8; 3] = b"dct";
-    pub const Save: &'static [u8; 4] = b"save";
-    pub const AdvertiseHighDuration: &'static [u8; 14] = b"set bl v h d 0";
-    pub const BLEPHYMultiplexFalse: &'static [u8; 12] = b"set bl p m 0";
-    pub const BLEPHYPreference1M: &'static [u8; 13] = b"set bl p p 1m";
-    pub const BLEEncryptionPairingAny: &'static [u8; 14] = b"set bl e p any";
-    pub const SystemRemoteCommandingFalse: &'static [u8; 12] = b"set sy r e 0";
     pub const BreakSequence: &'static [u8; 3] = b"$$$";
-    pub const SetDeviceName: &'static [u8; 21] = b"set sy d n JugglerBGX";
-    pub const SetModuleToMachineMode: &'static [u8; 18] = b"set sy c m machine";
-    pub const ClearAllBondings: &'static [u8; 4] = b"clrb";
+    pub const WakeByte: &'static [u8; 1] = b"\n";
+    pub const VariableOwnAddress: &'static str = "bl a";
+    pub const VariableBootloaderVersion: &'static str = "sy b v";
+    pub const VariableUuid: &'static str = "sy u";
+    pub const VariableFlowControl: &'static str = "ua f";
     pub const LINEBREAK: &'static [u8; 2] = b"\r\n";
-    pub const ConParams: &'static [u8; 10] = b"con params";
-    /*
-    !  Param Value\r\n
-    #  Addr  D0CF5E828DF6\r\n
-    #  Itvl  12\r\n
-    #  Mtu   250\r\n
-    #  Phy   1m\r\n
-    #  Tout  400\r\n
-    #  Err   0000\r\n
-    */
-    pub const SCAN: &'static [u8; 4] = b"scan";
-    /*
-    R000117\r\n
-    !  # RSSI BD_ADDR           Device Name\r\n
-    #  1  -47 d0:cf:5e:82:85:06 LOR-8090\r\n
-    #  2  -52 00:0d:6f:a7:a1:54 LOR-8090\r\n
-    */
-    pub const SCAN_RESULTS: &'static [u8; 12] = b"scan results";
+    // how often the break sequence is sent before giving up to reach the command mode
+    pub const RETRIES_COMMAND_MODE: u8 = 3;
+    // break sequences per rate while probing the baud rate, each one costs two guard times
+    pub const RETRIES_BAUD_RATE_SWEEP: u8 = 1;
+    // defaults of Timeouts which can be changed at runtime
     pub const TIMEOUT_COMMON: Duration = Duration::from_millis(30);
-    // change this to automatically change the BGX scan timeout and the read answer timeout
     pub const TIMEOUT_CONNECT_BGX_INTERN: u64 = 2;
-    // change this to modify the timeout for the read answer timeout
-    pub const TIMEOUT_CONNECT: Duration =
-        Duration::from_millis(100 + Command::TIMEOUT_CONNECT_BGX_INTERN * 1000);
     pub const TIMEOUT_DISCONNECT: Duration = Duration::from_millis(100);
     pub const TIMEOUT_SETTINGS: Duration = Duration::from_millis(500);
+    // with flow control writes may block while the module is busy, so only a stalled module is detected
+    pub const TIMEOUT_FLOW_CONTROL_STALL: Duration = Duration::from_secs(5);
+    // time until the boot banner has to be printed after a reboot
+    pub const TIMEOUT_BOOT: Duration = Duration::from_secs(5);
+    // commands to a remote module have to travel over the BLE link and back
+    pub const TIMEOUT_REMOTE: Duration = Duration::from_millis(500);
+    // answers to commands typed in the interactive terminal, long enough for a connection attempt
+    pub const TIMEOUT_INTERACTIVE: Duration = Duration::from_secs(3);
+    // enough for the advertising intervals of nearby modules to be seen a few times
+    pub const TIMEOUT_SCAN: Duration = Duration::from_secs(10);
+    // min. 500 ms silence on UART before and after the break sequence
+    pub const GUARD_TIME_BREAK: Duration = Duration::from_millis(550);
+    // silence after the wake byte until the module accepts input again
+    pub const GUARD_TIME_WAKE: Duration = Duration::from_millis(50);
+    // pause between two checks whether an application opened a virtual port, also the first pause before bridging it again after a failure
+    pub const INTERVAL_CONNECTION_POLL: Duration = Duration::from_millis(250);
+    // pause while advertising after which the host checks again whether a central connected
+    pub const INTERVAL_ADVERTISING_CHECK: Duration = Duration::from_secs(1);
+}
+
+#[test]
+fn bgx_command_to_bytes_1() {
+    let mac = "d0:cf:5e:82:85:06".parse().unwrap();
+
+    assert_eq!(
+        BgxCommand::Connect {
+            mac,
+            timeout_secs: 2
+        }
+        .to_bytes()
+        .unwrap(),
+        b"con d0cf5e828506 2"
+    );
+    assert_eq!(
+        BgxCommand::Advertise(AdvertisingMode::Low)
+            .to_bytes()
+            .unwrap(),
+        b"adv low"
+    );
+    assert_eq!(
+        BgxCommand::SetRemoteCommanding(false).to_bytes().unwrap(),
+        b"set sy r e 0"
+    );
+    assert_eq!(
+        BgxCommand::set("bl e p", "any").to_bytes().unwrap(),
+        b"set bl e p any"
+    );
+}
+
+#[test]
+fn changes_state_1() {
+    let mac = "d0cf5e828506".parse().unwrap();
+
+    assert!(BgxCommand::Connect {
+        mac,
+        timeout_secs: 2
+    }
+    .changes_state());
+    assert!(BgxCommand::Advertise(AdvertisingMode::High).changes_state());
+    assert!(BgxCommand::FactoryReset(mac).changes_state());
+    assert!(!BgxCommand::ConParams.changes_state());
+    assert!(!BgxCommand::AdvertiseOff.changes_state());
+    assert!(!BgxCommand::ScanResults.changes_state());
+    assert!(!BgxCommand::SetUartFlowControl(true).changes_state());
+}
+
+#[test]
+fn bgx_command_from_str_1() {
+    let mac: Mac = "d0cf5e828506".parse().unwrap();
+
+    for cmd in [
+        BgxCommand::Connect {
+            mac,
+            timeout_secs: 5,
+        },
+        BgxCommand::ConParams,
+        BgxCommand::Advertise(AdvertisingMode::Low),
+        BgxCommand::ScanResults,
+        BgxCommand::Get("sy d n".to_string()),
+        BgxCommand::set("bl v h d", "30"),
+        BgxCommand::FactoryReset(mac),
+    ] {
+        assert_eq!(cmd.to_string().parse(), Ok(cmd));
+    }
+    assert_eq!(" scan ".parse(), Ok(BgxCommand::Scan));
+
+    // by scan index or unknown to this crate
+    for line in ["con 1", "gfu 1 none", "set x", ""] {
+        assert_eq!(
+            line.parse::<BgxCommand>(),
+            Err(InvalidCommand::Unknown(line.to_string()))
+        );
+    }
+}
+
+#[test]
+fn bgx_command_validate_1() {
+    assert!(BgxCommand::SetDeviceName("JugglerBGX".to_string())
+        .to_bytes()
+        .is_ok());
+    assert_eq!(
+        BgxCommand::SetDeviceName("a name which is too long".to_string()).to_bytes(),
+        Err(InvalidCommand::DeviceName(
+            "a name which is too long".to_string()
+        ))
+    );
+    assert_eq!(
+        BgxCommand::SetAdvertiseLowInterval(20000).to_bytes(),
+        Err(InvalidCommand::AdvertisingInterval(20000))
+    );
+    assert_eq!(
+        BgxCommand::SetUartBaudRate(1234).to_bytes(),
+        Err(InvalidCommand::BaudRate(1234))
+    );
+    assert_eq!(
+        BgxCommand::SetDeviceName("my bgx".to_string()).to_bytes(),
+        Err(InvalidCommand::DeviceName("my bgx".to_string()))
+    );
+    assert!(BgxCommand::set("sy d n", "a\r\nreboot").to_bytes().is_err());
+    assert_eq!(
+        BgxCommand::set("ua  b", "9600").to_bytes(),
+        Err(InvalidCommand::HostSetting("ua  b".to_string()))
+    );
+    assert!(BgxCommand::SetUartBaudRate(9600).changes_host_settings());
+    assert!(!BgxCommand::set("sy d n", "bgx").changes_host_settings());
+    assert!(BgxCommand::Get(String::new()).to_bytes().is_err());
+}
+
+#[test]
+fn typed_command_parse_1() {
+    use crate::response_header::ResponseHeader;
+
+    let header = |response_code| ResponseHeader {
+        response_code,
+        data_length: 0,
+    };
+
+    assert_eq!(
+        OwnAddress
+            .parse(BgxResponse::DataWithHeader(
+                header(ResponseCodes::Success),
+                "d0cf5e828506\r\n".to_string()
+            ))
+            .unwrap(),
+        "d0cf5e828506".parse().unwrap()
+    );
+    assert_eq!(
+        ConParams
+            .parse(BgxResponse::DataWithHeader(
+                header(ResponseCodes::Success),
+                "!  Param Value\r\n#  Err   0208\r\n".to_string()
+            ))
+            .unwrap(),
+        None
+    );
+    assert_eq!(
+        BgxCommand::Save
+            .parse(BgxResponse::DataWithHeader(
+                header(ResponseCodes::InvalidArgument),
+                String::new()
+            ))
+            .unwrap_err()
+            .downcast_ref(),
+        Some(&ResponseCodes::InvalidArgument)
+    );
 }
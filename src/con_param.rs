@@ -12,7 +12,7 @@ use winnow::{
 use crate::{mac::Mac, response::BgxResponse};
 
 #[derive(Debug, PartialEq, Eq, Clone)]
-pub struct ConInfo(Mac);
+pub struct ConInfo(pub Mac);
 // only mac is used as other information is not relevant
 /*
 !  Param Value\r\n
@@ -0,0 +1,87 @@
+use anyhow::{anyhow, Result};
+use log::debug;
+use std::fmt::Display;
+
+use crate::{
+    bgx::Bgx13p,
+    command::{Command, OwnAddress},
+    mac::Mac,
+    response::ResponseCodes,
+};
+
+/// information the module reports about itself
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct DeviceInfo {
+    /// own BLE address
+    pub mac: Mac,
+    /// e.g. BGX13P
+    pub model: String,
+    /// full FW string, e.g. BGX13P.1.2.2738.2-1524-2738
+    pub firmware: String,
+    pub bootloader: Option<String>,
+    pub uuid: Option<String>,
+}
+
+impl Display for DeviceInfo {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {} ({})", self.model, self.mac, self.firmware)
+    }
+}
+
+impl Bgx13p {
+    /// queries the module for information about itself and caches it
+    pub fn device_info(&mut self) -> Result<DeviceInfo> {
+        self.switch_to_command_mode()?;
+
+        let firmware = self.read_fw_version()?;
+        let model = parse_model(&firmware)?;
+        let mac = self.execute(OwnAddress)?;
+        let bootloader = self.get_optional_variable(Command::VariableBootloaderVersion)?;
+        let uuid = self.get_optional_variable(Command::VariableUuid)?;
+
+        let info = DeviceInfo {
+            mac,
+            model,
+            firmware,
+            bootloader,
+            uuid,
+        };
+        debug!("Device info: {:?}", info);
+
+        self.device_info = Some(info.clone());
+
+        Ok(info)
+    }
+
+    /// reads a variable which isn't provided by every FW version
+    fn get_optional_variable(&mut self, variable: &str) -> Result<Option<String>> {
+        match self.get_variable(variable) {
+            Ok(v) => Ok(Some(v)),
+            Err(e) if e.downcast_ref() == Some(&ResponseCodes::UnknownVariableOrOption) => {
+                debug!("Variable {variable:?} not provided by FW");
+                Ok(None)
+            }
+            Err(e) => Err(e),
+        }
+    }
+}
+
+/// takes the model from the FW string, e.g. BGX13P from BGX13P.1.2.2738.2-1524-2738
+fn parse_model(firmware: &str) -> Result<String> {
+    firmware
+        .split('.')
+        .next()
+        .filter(|m| m.starts_with("BGX"))
+        .map(ToString::to_string)
+        .ok_or_else(|| anyhow!("Couldn't get model from FW string {firmware:?}"))
+}
+
+#[test]
+fn parse_model_1() {
+    assert_eq!(
+        parse_model("BGX13P.1.2.2738.2-1524-2738").unwrap(),
+        "BGX13P"
+    );
+    assert_eq!(parse_model("BGX13S.1.2.2045.0").unwrap(), "BGX13S");
+    assert!(parse_model("1.2.2738").is_err());
+}
@@ -0,0 +1,290 @@
+use anyhow::{anyhow, Result};
+use log::{debug, info, trace};
+use serialport::{SerialPortType, UsbPortInfo};
+use std::{
+    sync::mpsc::channel,
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::{bgx::Bgx13p, mac::Mac, timeouts::Timeouts};
+
+/// USB VID/PID pairs of the Silicon Labs CP210x USB to UART bridges used on BGX boards
+pub const DEFAULT_USB_IDS: [(u16, u16); 3] = [(0x10c4, 0xea60), (0x10c4, 0xea70), (0x10c4, 0xea71)];
+
+/// which USB serial ports are considered to be BGX modules
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DiscoveryConfig {
+    /// VID/PID pairs of the USB adapters
+    pub usb_ids: Vec<(u16, u16)>,
+    /// total time for probing all ports concurrently including the baud rate sweep,
+    /// unfinished ports are rejected and nothing is written to them anymore,
+    /// by default long enough for a module at the last probed rate
+    pub probe_budget: Duration,
+}
+
+impl Default for DiscoveryConfig {
+    fn default() -> Self {
+        Self {
+            usb_ids: DEFAULT_USB_IDS.to_vec(),
+            probe_budget: Bgx13p::max_detect_duration(&Timeouts::default()),
+        }
+    }
+}
+
+/// a port on which a BGX module answered
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DiscoveredModule {
+    pub port_name: String,
+    pub usb_serial: Option<String>,
+    pub product: Option<String>,
+    /// physical USB location like 1-1.2, only available on Linux
+    pub bus_location: Option<String>,
+    pub firmware: String,
+    /// own BLE address from the device info, which is read after the default settings if requested,
+    /// missing if the device info couldn't be read
+    pub mac: Option<Mac>,
+}
+
+/// a port which has been probed but isn't used as BGX module
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RejectedPort {
+    pub port_name: String,
+    pub reason: String,
+}
+
+/// outcome of probing all matching ports
+#[derive(Debug)]
+pub struct ProbeReport<T> {
+    pub accepted: Vec<T>,
+    pub rejected: Vec<RejectedPort>,
+}
+
+/// a USB serial port matching the configured VID/PID pairs
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct CandidatePort {
+    pub port_name: String,
+    pub usb: UsbPortInfo,
+}
+
+/// lists USB serial ports matching the configured VID/PID pairs without opening them
+pub(crate) fn candidate_ports(config: &DiscoveryConfig) -> Result<Vec<CandidatePort>> {
+    let ports = serialport::available_ports()?;
+    trace!("Detected the following ports: {:#?}", &ports);
+
+    Ok(ports
+        .into_iter()
+        .filter_map(|p| match p.port_type {
+            SerialPortType::UsbPort(usb) if config.usb_ids.contains(&(usb.vid, usb.pid)) => {
+                debug!("Found USB port {}: {:#?}", p.port_name, &usb);
+                Some(CandidatePort {
+                    port_name: p.port_name,
+                    usb,
+                })
+            }
+            _ => None,
+        })
+        .collect())
+}
+
+/// probes all matching ports concurrently and returns information about the modules found
+pub fn discover_modules(config: &DiscoveryConfig) -> Result<ProbeReport<DiscoveredModule>> {
+    Ok(probe_concurrently(
+        candidate_ports(config)?,
+        config.probe_budget,
+        |p, deadline| probe(p, false, deadline),
+    ))
+}
+
+/// probes all matching ports concurrently and opens the modules found
+pub fn detect_modules_with_report(config: &DiscoveryConfig) -> Result<ProbeReport<Bgx13p>> {
+    Ok(probe_concurrently(
+        candidate_ports(config)?,
+        config.probe_budget,
+        |p, deadline| Bgx13p::open_until(&p.port_name, deadline),
+    ))
+}
+
+/// runs the probe for each port in its own thread and waits for them at most the given budget, the probe gets its deadline
+fn probe_concurrently<T: Send + 'static>(
+    ports: Vec<CandidatePort>,
+    budget: Duration,
+    probe: fn(&CandidatePort, Instant) -> Result<T>,
+) -> ProbeReport<T> {
+    let deadline = Instant::now() + budget;
+    let (tx, rx) = channel();
+
+    for (i, port) in ports.iter().cloned().enumerate() {
+        let tx = tx.clone();
+        thread::spawn(move || {
+            // the receiver is gone if the budget has been exceeded
+            let _ = tx.send((i, probe(&port, deadline)));
+        });
+    }
+    drop(tx);
+
+    let mut results = ports.iter().map(|_| None).collect::<Vec<_>>();
+    while let Some(left) = deadline.checked_duration_since(Instant::now()) {
+        match rx.recv_timeout(left) {
+            Ok((i, res)) => {
+                if let Some(r) = results.get_mut(i) {
+                    *r = Some(res);
+                }
+            }
+            // either the budget is exceeded or all probes finished
+            Err(_) => break,
+        }
+    }
+
+    let mut report = ProbeReport {
+        accepted: Vec::new(),
+        rejected: Vec::new(),
+    };
+    for (port, res) in ports.into_iter().zip(results) {
+        let reason = match res {
+            Some(Ok(m)) => {
+                report.accepted.push(m);
+                continue;
+            }
+            Some(Err(e)) => format!("{e:#}"),
+            None => format!("Probe didn't finish within {budget:?}"),
+        };
+
+        info!(
+            "USB device {} not used as BGX due to: {}",
+            port.port_name, reason
+        );
+        report.rejected.push(RejectedPort {
+            port_name: port.port_name,
+            reason,
+        });
+    }
+
+    report
+}
+
+/// opens the module connected via the USB adapter with the given serial number
+pub fn open_by_usb_serial(usb_serial: &str, config: &DiscoveryConfig) -> Result<Bgx13p> {
+    let port = candidate_ports(config)?
+        .into_iter()
+        .find(|p| p.usb.serial_number.as_deref() == Some(usb_serial))
+        .ok_or_else(|| anyhow!("No USB adapter with serial number {usb_serial:?} found"))?;
+
+    Bgx13p::open(&port.port_name)
+}
+
+/**
+    Opens the port and requests the module information, optionally applies the default settings before.
+    Everything has to finish before the deadline, nothing is written to the module afterwards.
+*/
+pub(crate) fn probe(
+    port: &CandidatePort,
+    reach_well_known_state: bool,
+    deadline: Instant,
+) -> Result<DiscoveredModule> {
+    let mut bgx = Bgx13p::open_until(&port.port_name, deadline)?;
+
+    let (firmware, mac) = bgx.with_deadline(deadline, |bgx| {
+        if reach_well_known_state {
+            bgx.reach_well_known_state()?;
+        }
+
+        Ok(match bgx.device_info() {
+            Ok(i) => (i.firmware, Some(i.mac)),
+            Err(e) => {
+                debug!("No device info on {}: {e}", port.port_name);
+                (bgx.read_fw_version()?, None)
+            }
+        })
+    })?;
+
+    Ok(DiscoveredModule {
+        port_name: port.port_name.clone(),
+        usb_serial: port.usb.serial_number.clone(),
+        product: port.usb.product.clone(),
+        bus_location: bus_location(&port.port_name),
+        firmware,
+        mac,
+    })
+}
+
+/// resolves the USB location of a tty via sysfs, e.g. /dev/ttyUSB0 -> 1-1.2
+#[cfg(target_os = "linux")]
+fn bus_location(port_name: &str) -> Option<String> {
+    let tty = port_name.rsplit('/').next()?;
+    let device = std::fs::canonicalize(format!("/sys/class/tty/{tty}/device")).ok()?;
+
+    parse_bus_location(&device.to_string_lossy())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn bus_location(_port_name: &str) -> Option<String> {
+    None
+}
+
+/// takes the last USB port path from a sysfs device path, skipping the interface part as 1-1.2:1.0
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+fn parse_bus_location(device_path: &str) -> Option<String> {
+    device_path
+        .split('/')
+        .rfind(|c| {
+            c.split(':').next().is_some_and(|p| {
+                p.contains('-')
+                    && p.chars()
+                        .all(|c| c.is_ascii_digit() || c == '-' || c == '.')
+            })
+        })
+        .and_then(|c| c.split(':').next())
+        .map(ToString::to_string)
+}
+
+#[test]
+fn parse_bus_location_1() {
+    const CP2102: &str = "/sys/devices/pci0000:00/0000:00:14.0/usb1/1-1/1-1.2/1-1.2:1.0/ttyUSB0";
+    const CDC: &str = "/sys/devices/platform/soc/3f980000.usb/usb1/1-1/1-1:1.0";
+
+    assert_eq!(parse_bus_location(CP2102).as_deref(), Some("1-1.2"));
+    assert_eq!(parse_bus_location(CDC).as_deref(), Some("1-1"));
+    assert_eq!(parse_bus_location("/sys/devices/virtual/tty/tty0"), None);
+}
+
+#[test]
+fn probe_concurrently_1() {
+    let port = |name: &str| CandidatePort {
+        port_name: name.to_string(),
+        usb: UsbPortInfo {
+            vid: 0x10c4,
+            pid: 0xea60,
+            serial_number: None,
+            manufacturer: None,
+            product: None,
+        },
+    };
+    let ports = vec![port("ok"), port("err"), port("slow")];
+
+    let report = probe_concurrently(ports, Duration::from_millis(200), |p, _| {
+        match p.port_name.as_str() {
+            "ok" => Ok(p.port_name.clone()),
+            "err" => Err(anyhow!("no answer")),
+            _ => {
+                thread::sleep(Duration::from_secs(1));
+                Ok(p.port_name.clone())
+            }
+        }
+    });
+
+    assert_eq!(report.accepted, vec!["ok".to_string()]);
+    assert_eq!(
+        report.rejected,
+        vec![
+            RejectedPort {
+                port_name: "err".to_string(),
+                reason: "no answer".to_string()
+            },
+            RejectedPort {
+                port_name: "slow".to_string(),
+                reason: "Probe didn't finish within 200ms".to_string()
+            },
+        ]
+    );
+}
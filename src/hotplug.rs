@@ -0,0 +1,399 @@
+use anyhow::{anyhow, Result};
+use log::{debug, info, warn};
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+use crate::discovery::{candidate_ports, probe, CandidatePort, DiscoveredModule, DiscoveryConfig};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HotplugEvent {
+    ModuleAdded(DiscoveredModule),
+    /// contains the information gathered when the module was added
+    ModuleRemoved(DiscoveredModule),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HotplugOptions {
+    pub discovery: DiscoveryConfig,
+    /// rescan interval if udev isn't available
+    pub poll_interval: Duration,
+    /// apply the default settings to newly attached modules before reporting them
+    pub reach_well_known_state: bool,
+}
+
+impl Default for HotplugOptions {
+    fn default() -> Self {
+        Self {
+            discovery: Default::default(),
+            poll_interval: Duration::from_secs(2),
+            reach_well_known_state: false,
+        }
+    }
+}
+
+/**
+    Watches for BGX adapters being plugged in or out in a background thread.
+    Uses udev events on Linux and falls back to polling the available ports otherwise.
+    Modules which are already attached are reported as added right after starting.
+    Stopping waits for a running probe only, the remaining ports aren't probed anymore.
+*/
+pub struct HotplugWatcher {
+    stop: Arc<AtomicBool>,
+    handle: Option<JoinHandle<()>>,
+}
+
+/// probes a port, see [`probe`]
+type Probe = fn(&CandidatePort, bool, Instant) -> Result<DiscoveredModule>;
+
+impl HotplugWatcher {
+    pub fn start(
+        options: HotplugOptions,
+        on_event: impl FnMut(HotplugEvent) + Send + 'static,
+    ) -> Self {
+        let (poll_interval, discovery) = (options.poll_interval, options.discovery.clone());
+
+        Self::start_with(
+            options,
+            move || RescanTrigger::new(poll_interval, discovery),
+            probe,
+            on_event,
+        )
+    }
+
+    /// the source is created in the watcher thread as the udev socket has to stay there
+    fn start_with<S: PortSource>(
+        options: HotplugOptions,
+        source: impl FnOnce() -> S + Send + 'static,
+        probe: Probe,
+        mut on_event: impl FnMut(HotplugEvent) + Send + 'static,
+    ) -> Self {
+        let stop = Arc::new(AtomicBool::new(false));
+        let stopped = stop.clone();
+
+        let handle = thread::spawn(move || {
+            let mut source = source();
+            let mut known = HashMap::new();
+            let mut rejected = HashSet::new();
+
+            while !stopped.load(Ordering::Relaxed) {
+                if source.rescan_needed() {
+                    match source.ports() {
+                        Ok(ports) => rescan(
+                            &options,
+                            ports,
+                            probe,
+                            &mut known,
+                            &mut rejected,
+                            &stopped,
+                            &mut on_event,
+                        ),
+                        Err(e) => warn!("Couldn't scan for BGX adapters: {e}"),
+                    }
+                }
+
+                thread::sleep(RescanTrigger::CHECK_INTERVAL);
+            }
+        });
+
+        Self {
+            stop,
+            handle: Some(handle),
+        }
+    }
+
+    pub fn stop(mut self) -> Result<()> {
+        self.shutdown()
+    }
+
+    fn shutdown(&mut self) -> Result<()> {
+        if let Some(handle) = self.handle.take() {
+            self.stop.store(true, Ordering::Relaxed);
+            handle
+                .join()
+                .map_err(|_| anyhow!("Hotplug watcher thread panicked"))?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Drop for HotplugWatcher {
+    fn drop(&mut self) {
+        if let Err(e) = self.shutdown() {
+            warn!("Couldn't stop hotplug watcher: {e}");
+        }
+    }
+}
+
+/// compares the attached adapters with the known ones and reports the differences, stops between probes if requested
+fn rescan(
+    options: &HotplugOptions,
+    ports: Vec<CandidatePort>,
+    probe: Probe,
+    known: &mut HashMap<String, DiscoveredModule>,
+    rejected: &mut HashSet<String>,
+    stop: &AtomicBool,
+    on_event: &mut impl FnMut(HotplugEvent),
+) {
+    let attached = |port_name: &String| ports.iter().any(|p| &p.port_name == port_name);
+
+    let removed = known
+        .keys()
+        .filter(|k| !attached(k))
+        .cloned()
+        .collect::<Vec<_>>();
+    for port_name in removed {
+        if let Some(m) = known.remove(&port_name) {
+            info!("BGX adapter on {port_name} removed");
+            on_event(HotplugEvent::ModuleRemoved(m));
+        }
+    }
+    // a rejected port is probed again once it has been plugged in again
+    rejected.retain(|r| attached(r));
+
+    for port in ports {
+        if known.contains_key(&port.port_name) || rejected.contains(&port.port_name) {
+            continue;
+        }
+        if stop.load(Ordering::Relaxed) {
+            debug!("Stop requested, skip probing the remaining ports");
+            return;
+        }
+
+        let deadline = Instant::now() + options.discovery.probe_budget;
+        match probe(&port, options.reach_well_known_state, deadline) {
+            Ok(m) => {
+                info!("BGX adapter on {} added", port.port_name);
+                known.insert(port.port_name, m.clone());
+                on_event(HotplugEvent::ModuleAdded(m));
+            }
+            Err(e) => {
+                info!(
+                    "USB device {} not used as BGX due to: {}",
+                    port.port_name, e
+                );
+                rejected.insert(port.port_name);
+            }
+        }
+    }
+}
+
+/// tells when and which adapters are attached
+trait PortSource {
+    /// whether the attached adapters have to be scanned again
+    fn rescan_needed(&mut self) -> bool;
+
+    fn ports(&mut self) -> Result<Vec<CandidatePort>>;
+}
+
+/// decides when the attached adapters have to be scanned again
+struct RescanTrigger {
+    #[cfg(target_os = "linux")]
+    udev: Option<libudev::MonitorSocket>,
+    poll_interval: Duration,
+    last_scan: Option<Instant>,
+    discovery: DiscoveryConfig,
+}
+
+impl RescanTrigger {
+    /// how often udev events and the stop request are checked
+    const CHECK_INTERVAL: Duration = Duration::from_millis(200);
+
+    fn new(poll_interval: Duration, discovery: DiscoveryConfig) -> Self {
+        Self {
+            #[cfg(target_os = "linux")]
+            udev: Self::udev_monitor()
+                .map_err(|e| warn!("No udev available, fall back to polling: {e}"))
+                .ok(),
+            poll_interval,
+            last_scan: None,
+            discovery,
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    fn udev_monitor() -> Result<libudev::MonitorSocket> {
+        let context = libudev::Context::new()?;
+        let mut monitor = libudev::Monitor::new(&context)?;
+        monitor.match_subsystem("tty")?;
+
+        Ok(monitor.listen()?)
+    }
+
+    #[cfg(target_os = "linux")]
+    fn udev_active(&self) -> bool {
+        self.udev.is_some()
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    fn udev_active(&self) -> bool {
+        false
+    }
+
+    /// drains all pending udev events and returns whether there has been one
+    #[cfg(target_os = "linux")]
+    fn udev_event(&mut self) -> bool {
+        let mut any = false;
+
+        if let Some(socket) = self.udev.as_mut() {
+            while let Some(event) = socket.receive_event() {
+                debug!(
+                    "udev {:?} event for {:?}",
+                    event.event_type(),
+                    event.syspath()
+                );
+                any = true;
+            }
+        }
+
+        any
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    fn udev_event(&mut self) -> bool {
+        false
+    }
+}
+
+impl PortSource for RescanTrigger {
+    fn rescan_needed(&mut self) -> bool {
+        // always drain the events so that old ones don't trigger later
+        let udev_event = self.udev_event();
+
+        let due = match self.last_scan {
+            None => true,
+            Some(_) if self.udev_active() => udev_event,
+            Some(t) => t.elapsed() >= self.poll_interval,
+        };
+
+        if due {
+            self.last_scan = Some(Instant::now());
+        }
+
+        due
+    }
+
+    fn ports(&mut self) -> Result<Vec<CandidatePort>> {
+        candidate_ports(&self.discovery)
+    }
+}
+
+/// adapters without USB details, the name decides whether a module answers, see [`probe_named`]
+#[cfg(test)]
+fn candidates(names: &[&str]) -> Vec<CandidatePort> {
+    names
+        .iter()
+        .map(|name| CandidatePort {
+            port_name: name.to_string(),
+            usb: serialport::UsbPortInfo {
+                vid: 0x10c4,
+                pid: 0xea60,
+                serial_number: None,
+                manufacturer: None,
+                product: None,
+            },
+        })
+        .collect()
+}
+
+/// a module answers on ports whose name starts with "bgx"
+#[cfg(test)]
+fn probe_named(port: &CandidatePort, _reach: bool, _deadline: Instant) -> Result<DiscoveredModule> {
+    if !port.port_name.starts_with("bgx") {
+        return Err(anyhow!("No module answered"));
+    }
+
+    Ok(DiscoveredModule {
+        port_name: port.port_name.clone(),
+        usb_serial: None,
+        product: None,
+        bus_location: None,
+        firmware: "BGX13P.1.2.2738.2-1524-2738".to_string(),
+        mac: None,
+    })
+}
+
+#[test]
+fn hotplug_watcher_1() {
+    use std::{collections::VecDeque, sync::mpsc::channel};
+
+    /// attached adapters of each scan, one after the other
+    struct Scripted(VecDeque<Vec<CandidatePort>>);
+    impl PortSource for Scripted {
+        fn rescan_needed(&mut self) -> bool {
+            !self.0.is_empty()
+        }
+        fn ports(&mut self) -> Result<Vec<CandidatePort>> {
+            Ok(self.0.pop_front().unwrap_or_default())
+        }
+    }
+
+    static PROBED_OTHER: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
+    fn probe_counted(
+        port: &CandidatePort,
+        reach: bool,
+        deadline: Instant,
+    ) -> Result<DiscoveredModule> {
+        if port.port_name == "other" {
+            PROBED_OTHER.fetch_add(1, Ordering::Relaxed);
+        }
+        probe_named(port, reach, deadline)
+    }
+
+    // both adapters are unplugged and plugged in again, the rejected one is probed first
+    let scans = [vec!["other", "bgx0"], vec![], vec!["other", "bgx0"]];
+    let source = Scripted(scans.iter().map(|s| candidates(s)).collect());
+    let (tx, rx) = channel();
+    let watcher = HotplugWatcher::start_with(
+        Default::default(),
+        move || source,
+        probe_counted,
+        move |e| tx.send(e).unwrap(),
+    );
+
+    let events = (0..3)
+        .map(|_| rx.recv_timeout(Duration::from_secs(5)).unwrap())
+        .collect::<Vec<_>>();
+    watcher.stop().unwrap();
+
+    let bgx0 = candidates(&["bgx0"]).pop().unwrap();
+    let module = probe_named(&bgx0, false, Instant::now()).unwrap();
+    assert_eq!(
+        events,
+        vec![
+            HotplugEvent::ModuleAdded(module.clone()),
+            HotplugEvent::ModuleRemoved(module.clone()),
+            HotplugEvent::ModuleAdded(module),
+        ]
+    );
+    assert_eq!(PROBED_OTHER.load(Ordering::Relaxed), 2);
+}
+
+#[test]
+fn rescan_stop_1() {
+    let stop = AtomicBool::new(false);
+    let mut known = HashMap::new();
+    let mut rejected = HashSet::new();
+
+    // the stop request arrives while the first module is probed
+    rescan(
+        &Default::default(),
+        candidates(&["bgx0", "other"]),
+        probe_named,
+        &mut known,
+        &mut rejected,
+        &stop,
+        &mut |_| stop.store(true, Ordering::Relaxed),
+    );
+
+    assert!(known.contains_key("bgx0"));
+    // the second port hasn't been probed, it would have been rejected otherwise
+    assert!(rejected.is_empty());
+}
@@ -0,0 +1,144 @@
+use crate::{
+    response::{parse_next_response, BgxResponse, ResponseCodes},
+    response_header::ResponseHeader,
+    state::ProtocolMode,
+};
+
+// status lines the module prints in human mode, matched case insensitive at the start of the last line
+const STATUS_LINES: [(&str, ResponseCodes); 10] = [
+    ("success", ResponseCodes::Success),
+    ("command failed", ResponseCodes::CommandFailed),
+    ("parse error", ResponseCodes::ParseError),
+    ("unknown command", ResponseCodes::UnknownCommand),
+    ("too few arg", ResponseCodes::TooFewArguments),
+    ("too many arg", ResponseCodes::TooManyArguments),
+    ("unknown variable", ResponseCodes::UnknownVariableOrOption),
+    ("invalid argument", ResponseCodes::InvalidArgument),
+    ("timeout", ResponseCodes::Timeout),
+    ("security mismatch", ResponseCodes::SecurityMismatch),
+];
+
+/// detects the mode from any answer of the module, machine mode if it contains a header
+pub fn detect_protocol_mode(answer: &[u8]) -> ProtocolMode {
+    let mut rest = answer;
+    while let Some((resp, consumed)) = parse_next_response(rest, true) {
+        if let BgxResponse::DataWithHeader(..) = resp {
+            return ProtocolMode::Machine;
+        }
+        rest = rest.get(consumed..).unwrap_or_default();
+    }
+
+    ProtocolMode::Human
+}
+
+/**
+    Turns a human mode answer into a response as it would be sent in machine mode.
+    The echoed command, prompts and the status line are removed, the status line gives the response code.
+*/
+pub fn parse_human_response(answer: &[u8], command: &[u8]) -> (ResponseHeader, String) {
+    let answer = String::from_utf8_lossy(answer);
+    let command = String::from_utf8_lossy(command);
+
+    let mut lines = answer
+        .lines()
+        .map(|l| l.trim_start_matches('>').trim())
+        .filter(|l| !l.is_empty() && *l != command.trim() && *l != "Ready")
+        .collect::<Vec<_>>();
+
+    let status = lines.last().and_then(|l| {
+        let l = l.to_lowercase();
+        STATUS_LINES
+            .iter()
+            .find(|(s, _)| l.starts_with(s))
+            .map(|(_, code)| *code)
+    });
+    if status.is_some() {
+        lines.pop();
+    }
+
+    let data = lines.iter().map(|l| format!("{l}\r\n")).collect::<String>();
+
+    (
+        ResponseHeader {
+            response_code: status.unwrap_or(ResponseCodes::Success),
+            data_length: u32::try_from(data.len()).unwrap_or(u32::MAX),
+        },
+        data,
+    )
+}
+
+#[test]
+fn detect_protocol_mode_1() {
+    assert_eq!(
+        detect_protocol_mode(b"R000029\r\nBGX13P.1.2.2738.2-1524-2738\r\n"),
+        ProtocolMode::Machine
+    );
+    assert_eq!(
+        detect_protocol_mode(b"ver\r\nBGX13P.1.2.2738.2-1524-2738\r\n"),
+        ProtocolMode::Human
+    );
+}
+
+#[test]
+fn parse_human_response_1() {
+    let response = |code, data: &str| {
+        (
+            ResponseHeader {
+                response_code: code,
+                data_length: data.len() as u32,
+            },
+            data.to_string(),
+        )
+    };
+
+    assert_eq!(
+        parse_human_response(b"get sy d n\r\nJugglerBGX\r\n> ", b"get sy d n"),
+        response(ResponseCodes::Success, "JugglerBGX\r\n")
+    );
+    assert_eq!(
+        parse_human_response(b"set sy d n x\r\nSuccess\r\n", b"set sy d n x"),
+        response(ResponseCodes::Success, "")
+    );
+    assert_eq!(
+        parse_human_response(b"foo\r\nUnknown command\r\n", b"foo"),
+        response(ResponseCodes::UnknownCommand, "")
+    );
+    assert_eq!(
+        parse_human_response(b"get x\r\nUnknown variable or option\r\n", b"get x"),
+        response(ResponseCodes::UnknownVariableOrOption, "")
+    );
+}
+
+#[test]
+fn human_mode_replay_1() {
+    use crate::{
+        bgx::Bgx13p,
+        recording::{replay, Traffic},
+    };
+
+    let traffic = [
+        Traffic::ReadTimeouts(1),
+        Traffic::Sent(b"\r\n".to_vec()),
+        Traffic::Sent(b"\r\n".to_vec()),
+        Traffic::Received(b"Ready\r\nReady\r\n".to_vec()),
+        Traffic::ReadTimeouts(1),
+        // echo and answer without header
+        Traffic::Sent(b"ver\r\n".to_vec()),
+        Traffic::Received(b"ver\r\nBGX13P.1.2.2738.2-1524-2738\r\n".to_vec()),
+        Traffic::ReadTimeouts(1),
+        Traffic::Sent(b"get ua f\r\n".to_vec()),
+        Traffic::Received(b"get ua f\r\noff\r\n".to_vec()),
+        Traffic::ReadTimeouts(1),
+        Traffic::Sent(b"get sy x\r\n".to_vec()),
+        Traffic::Received(b"get sy x\r\nUnknown variable or option\r\n".to_vec()),
+        Traffic::ReadTimeouts(1),
+    ];
+    let mut bgx = Bgx13p::open_with_port(replay(traffic)).unwrap();
+    assert_eq!(bgx.protocol_mode(), Some(ProtocolMode::Human));
+
+    let err = bgx.get("sy x").unwrap_err();
+    assert_eq!(
+        err.downcast_ref::<ResponseCodes>(),
+        Some(&ResponseCodes::UnknownVariableOrOption)
+    );
+}
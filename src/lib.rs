@@ -2,12 +2,32 @@
 #![allow(non_upper_case_globals)]
 #![forbid(clippy::indexing_slicing)]
 
+pub mod advertising;
 pub mod bgx;
-mod command;
-mod con_param;
+pub mod bridge;
+pub mod command;
+pub mod con_param;
+pub mod device_info;
+pub mod discovery;
 mod fw;
+pub mod hotplug;
+mod human;
 pub mod mac;
-mod response;
-mod response_header;
-mod scan;
-mod scanned_device;
+pub mod power;
+#[cfg(target_os = "linux")]
+pub mod pty_bridge;
+pub mod raw;
+pub mod recording;
+pub mod remote;
+mod reset;
+pub mod response;
+pub mod response_header;
+pub mod scan;
+pub mod scanned_device;
+pub mod settings;
+pub mod state;
+pub mod supervisor;
+pub mod tcp_bridge;
+#[cfg(feature = "terminal")]
+pub mod terminal;
+pub mod timeouts;
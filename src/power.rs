@@ -0,0 +1,122 @@
+use anyhow::Result;
+use log::{debug, info};
+use std::{thread::sleep, time::Duration};
+
+use crate::{
+    advertising::AdvertisingSettings,
+    bgx::Bgx13p,
+    command::{BgxCommand, Command},
+    state::ModuleState,
+};
+
+/// settings for running the module with low power consumption
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PowerSettings {
+    /// seconds without activity until the module goes to sleep on its own, 0 disables it
+    pub idle_timeout: u32,
+    pub advertising: AdvertisingSettings,
+}
+
+impl Default for PowerSettings {
+    /// sleeps after a minute and advertises fast for 30 s only, then once per second
+    fn default() -> Self {
+        Self {
+            idle_timeout: 60,
+            advertising: AdvertisingSettings {
+                high_duration: 30,
+                high_interval: 32,
+                low_duration: 0,
+                low_interval: 1600,
+            },
+        }
+    }
+}
+
+impl Bgx13p {
+    /// closes an active connection and puts the module into its sleep state
+    pub fn sleep(&mut self) -> Result<()> {
+        self.disconnect()?;
+        self.switch_to_command_mode()?;
+
+        self.write_expect_success(&BgxCommand::Sleep, None)?;
+        self.state = ModuleState::Asleep;
+        self.advertising = false;
+        info!("Module is asleep");
+
+        Ok(())
+    }
+
+    /// wakes the module up and brings it into command mode
+    pub fn wake(&mut self) -> Result<()> {
+        debug!("Wake module...");
+
+        // the first byte only wakes up the UART and gets lost, the module needs a guard time afterwards
+        self.write_all_with_timeout(Command::WakeByte, None)?;
+        sleep(Command::GUARD_TIME_WAKE);
+
+        self.state = ModuleState::Unknown;
+        self.switch_to_command_mode()?;
+        info!("Module woke up");
+
+        Ok(())
+    }
+
+    /**
+        Configures idle timeout and advertising for low power, settings are saved.
+        The module is woken up first when it has been idle for longer than the timeout in command mode.
+    */
+    pub fn set_power_settings(&mut self, settings: &PowerSettings) -> Result<()> {
+        self.set_advertising_settings(&settings.advertising)?;
+
+        self.write_expect_success(
+            &BgxCommand::SetIdleTimeout(settings.idle_timeout),
+            self.timeouts.settings,
+        )?;
+        self.idle_timeout =
+            (settings.idle_timeout > 0).then(|| Duration::from_secs(settings.idle_timeout.into()));
+
+        self.write_expect_success(&BgxCommand::Save, self.timeouts.settings)
+    }
+}
+
+#[test]
+fn power_settings_default_1() {
+    assert!(PowerSettings::default().advertising.validate().is_ok());
+}
+
+#[test]
+fn set_power_settings_1() {
+    use crate::recording::Traffic;
+
+    let success = || Traffic::Received(b"R000000\r\n".to_vec());
+    let mut bgx = crate::recording::replay_opened([
+        Traffic::Sent(b"set bl v h d 30\r\n".to_vec()),
+        success(),
+        Traffic::Sent(b"set bl v h i 32\r\n".to_vec()),
+        success(),
+        Traffic::Sent(b"set bl v l d 0\r\n".to_vec()),
+        success(),
+        Traffic::Sent(b"set bl v l i 1600\r\n".to_vec()),
+        success(),
+        Traffic::Sent(b"set sy s t 60\r\n".to_vec()),
+        success(),
+        Traffic::Sent(b"save\r\n".to_vec()),
+        success(),
+        // the module fell asleep in the meantime
+        Traffic::Sent(b"\n".to_vec()),
+        Traffic::ReadTimeouts(1),
+        Traffic::Sent(b"\r\n".to_vec()),
+        Traffic::Sent(b"\r\n".to_vec()),
+        Traffic::Received(b"Ready\r\nReady\r\n".to_vec()),
+        Traffic::ReadTimeouts(1),
+        Traffic::Sent(b"get sy d n\r\n".to_vec()),
+        Traffic::Received(b"R000012\r\nJugglerBGX\r\n".to_vec()),
+    ]);
+
+    bgx.set_power_settings(&PowerSettings::default()).unwrap();
+    assert_eq!(bgx.idle_timeout, Some(Duration::from_secs(60)));
+
+    bgx.last_activity -= Duration::from_secs(61);
+    assert_eq!(bgx.get("sy d n").unwrap(), "JugglerBGX");
+    assert_eq!(bgx.state(), ModuleState::Command);
+}
@@ -0,0 +1,271 @@
+use anyhow::{anyhow, Context, Result};
+use log::{info, warn};
+use nix::{
+    poll::{poll, PollFd, PollFlags, PollTimeout},
+    pty::openpty,
+    sys::termios::{cfmakeraw, tcgetattr, tcsetattr, SetArg},
+    unistd::ttyname,
+};
+use std::{
+    fs::File,
+    io::{self, ErrorKind, Read, Write},
+    os::fd::AsFd,
+    path::{Path, PathBuf},
+    thread::sleep,
+    time::Duration,
+};
+
+use crate::{bgx::Bgx13p, bridge::BridgeEnd, command::Command, mac::Mac};
+
+/// longest pause before bridging again after a failure, the pause doubles from [`Command::INTERVAL_CONNECTION_POLL`]
+const MAX_RETRY_INTERVAL: Duration = Duration::from_secs(30);
+
+/**
+    Pseudo-terminal which looks like a local serial port to applications.
+    The optional symlink gives it a stable path and is removed again on drop.
+*/
+pub struct VirtualPort {
+    master: File,
+    tty: PathBuf,
+    link: Option<PathBuf>,
+    read_timeout: PollTimeout,
+}
+
+impl VirtualPort {
+    pub fn create(link: Option<PathBuf>) -> Result<Self> {
+        let pty = openpty(None, None)?;
+
+        // bytes have to be passed unchanged, applications may still change this after opening
+        let mut termios = tcgetattr(&pty.slave)?;
+        cfmakeraw(&mut termios);
+        tcsetattr(&pty.slave, SetArg::TCSANOW, &termios)?;
+
+        let tty = ttyname(&pty.slave)?;
+        info!("Created pseudo-terminal {}", tty.display());
+
+        if let Some(link) = &link {
+            // only replace stale symlinks whose pseudo-terminal is gone, never a real file or a live link
+            if link.is_symlink() && !link.exists() {
+                std::fs::remove_file(link)?;
+            } else if link.is_symlink() {
+                return Err(anyhow!(
+                    "Symlink {} is in use by another pseudo-terminal",
+                    link.display()
+                ));
+            }
+            std::os::unix::fs::symlink(&tty, link)
+                .with_context(|| format!("Couldn't create symlink {}", link.display()))?;
+        }
+
+        // the slave is closed so that a hangup shows whether an application has it open
+        drop(pty.slave);
+
+        Ok(Self {
+            master: pty.master.into(),
+            tty,
+            link,
+            read_timeout: PollTimeout::try_from(Command::TIMEOUT_COMMON)?,
+        })
+    }
+
+    /// path applications should open, the symlink if there is one
+    pub fn path(&self) -> &Path {
+        self.link.as_deref().unwrap_or(&self.tty)
+    }
+
+    /// how long a read waits for the application before returning `WouldBlock`
+    pub fn set_read_timeout(&mut self, timeout: Duration) -> Result<()> {
+        self.read_timeout = PollTimeout::try_from(timeout)?;
+
+        Ok(())
+    }
+
+    /**
+        Closes the pseudo-terminal so that the application sees a hang-up and replaces it with a new one.
+        The symlink points to the new one afterwards, without symlink the path changes.
+    */
+    pub fn hang_up(&mut self) -> Result<()> {
+        let link = self.link.take();
+        if let Some(link) = &link {
+            std::fs::remove_file(link)?;
+        }
+
+        let read_timeout = self.read_timeout;
+        *self = Self::create(link)?;
+        self.read_timeout = read_timeout;
+
+        Ok(())
+    }
+
+    /// whether no application has the port open
+    fn hung_up(&self) -> io::Result<bool> {
+        Ok(self.poll(PollTimeout::ZERO)?.contains(PollFlags::POLLHUP))
+    }
+
+    fn poll(&self, timeout: PollTimeout) -> io::Result<PollFlags> {
+        let mut fds = [PollFd::new(self.master.as_fd(), PollFlags::POLLIN)];
+        poll(&mut fds, timeout)?;
+
+        Ok(fds
+            .first()
+            .and_then(|fd| fd.revents())
+            .unwrap_or(PollFlags::empty()))
+    }
+}
+
+impl Read for VirtualPort {
+    /// returns `WouldBlock` if the application didn't write anything within the read timeout
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let events = self.poll(self.read_timeout)?;
+
+        if events.contains(PollFlags::POLLIN) {
+            self.master.read(buf)
+        } else if events.contains(PollFlags::POLLHUP) {
+            Err(io::Error::new(
+                ErrorKind::BrokenPipe,
+                "Application closed the port",
+            ))
+        } else {
+            Err(ErrorKind::WouldBlock.into())
+        }
+    }
+}
+
+impl Write for VirtualPort {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.master.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.master.flush()
+    }
+}
+
+impl Drop for VirtualPort {
+    fn drop(&mut self) {
+        if let Some(link) = &self.link {
+            if let Err(e) = std::fs::remove_file(link) {
+                warn!("Couldn't remove symlink {}: {e}", link.display());
+            }
+        }
+    }
+}
+
+impl Bgx13p {
+    /**
+        Waits for applications to open the virtual port and bridges it to the target while it's open, see [`Bgx13p::bridge`].
+        The link is closed when the application closes the port.
+        If the link drops or can't be established, the port hangs up, see [`VirtualPort::hang_up`],
+        and bridging is retried once it's opened again, after a pause which grows with each failure.
+    */
+    pub fn serve_pty(
+        &mut self,
+        port: &mut VirtualPort,
+        target: &Mac,
+        idle_check: Duration,
+    ) -> Result<()> {
+        port.set_read_timeout(self.timeouts.common)?;
+        info!("Waiting for applications to open {}", port.path().display());
+
+        let mut retry_interval = Command::INTERVAL_CONNECTION_POLL;
+        loop {
+            if port.hung_up()? {
+                sleep(Command::INTERVAL_CONNECTION_POLL);
+                continue;
+            }
+
+            info!("{} opened, bridge to {target}", port.path().display());
+            match self.bridge(port, target, idle_check) {
+                Ok(BridgeEnd::ClientLeft) => {
+                    info!("{} closed", port.path().display());
+                    retry_interval = Command::INTERVAL_CONNECTION_POLL;
+                }
+                Ok(BridgeEnd::LinkLost) => {
+                    warn!("Link to {target} lost, hang up");
+                    port.hang_up()?;
+                    retry_interval = Command::INTERVAL_CONNECTION_POLL;
+                }
+                Err(e) => {
+                    warn!(
+                        "Bridge to {target} failed, hang up and retry in {retry_interval:?}: {e:#}"
+                    );
+                    port.hang_up()?;
+                    sleep(retry_interval);
+                    retry_interval = (retry_interval * 2).min(MAX_RETRY_INTERVAL);
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn virtual_port_1() {
+    let mut port = VirtualPort::create(None).unwrap();
+    assert!(port.hung_up().unwrap());
+
+    let mut app = std::fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open(port.path())
+        .unwrap();
+    assert!(!port.hung_up().unwrap());
+
+    let mut buf = [0u8; 16];
+    assert_eq!(
+        port.read(&mut buf).unwrap_err().kind(),
+        ErrorKind::WouldBlock
+    );
+
+    app.write_all(b"ab\n").unwrap();
+    let n = port.read(&mut buf).unwrap();
+    assert_eq!(buf.get(..n), Some(&b"ab\n"[..]));
+
+    drop(app);
+    assert!(port.hung_up().unwrap());
+}
+
+#[test]
+fn virtual_port_link_1() {
+    let link = std::env::temp_dir().join(format!("bgx-link-{}", std::process::id()));
+    let _ = std::fs::remove_file(&link);
+
+    // a stale link is replaced
+    std::os::unix::fs::symlink("/nonexistent/bgx", &link).unwrap();
+    let port = VirtualPort::create(Some(link.clone())).unwrap();
+    assert_eq!(std::fs::read_link(&link).unwrap(), port.tty);
+
+    // the live link of another port is kept
+    assert!(VirtualPort::create(Some(link.clone())).is_err());
+    assert_eq!(std::fs::read_link(&link).unwrap(), port.tty);
+
+    drop(port);
+    assert!(!link.is_symlink());
+}
+
+#[test]
+fn virtual_port_hang_up_1() {
+    let link = std::env::temp_dir().join(format!("bgx-hang-up-{}", std::process::id()));
+    let _ = std::fs::remove_file(&link);
+
+    let mut port = VirtualPort::create(Some(link.clone())).unwrap();
+    port.set_read_timeout(Duration::from_millis(10)).unwrap();
+    let mut app = std::fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open(port.path())
+        .unwrap();
+    let old_tty = port.tty.clone();
+
+    port.hang_up().unwrap();
+
+    // the application sees the hang-up, the symlink leads to the new pseudo-terminal
+    let mut buf = [0u8; 16];
+    assert!(!matches!(app.read(&mut buf), Ok(n) if n > 0));
+    assert_eq!(std::fs::read_link(&link).unwrap(), port.tty);
+    assert_ne!(port.tty, old_tty);
+    assert!(port.hung_up().unwrap());
+    assert_eq!(port.read_timeout, PollTimeout::from(10u8));
+
+    drop(port);
+    assert!(!link.is_symlink());
+}
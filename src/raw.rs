@@ -0,0 +1,138 @@
+use anyhow::Result;
+use log::debug;
+use std::time::Duration;
+use thiserror::Error;
+
+use crate::{
+    bgx::Bgx13p,
+    command::{BgxCommand, InvalidCommand},
+    response::ResponseCodes,
+    response_header::ResponseHeader,
+};
+
+/// answer to a command sent with [`Bgx13p::raw_command`]
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct RawResponse {
+    /// response code and announced length, synthesized from the status line in human mode
+    pub header: ResponseHeader,
+    /// data following the header as received
+    pub payload: Vec<u8>,
+}
+
+/// answers which couldn't be taken apart, the received bytes are kept for inspection
+#[derive(Debug, PartialEq, Eq, Error)]
+pub enum RawResponseError {
+    #[error("No complete response within {timeout:?}, got: {raw:?}")]
+    Incomplete { timeout: Duration, raw: Vec<u8> },
+    #[error("Payload is not valid UTF-8: {raw:?}")]
+    NotText { raw: Vec<u8> },
+}
+
+impl RawResponse {
+    pub fn is_success(&self) -> bool {
+        self.header.response_code == ResponseCodes::Success
+    }
+
+    /// payload as text, most commands answer with lines of text
+    pub fn text(&self) -> Result<&str, RawResponseError> {
+        std::str::from_utf8(&self.payload).map_err(|_| RawResponseError::NotText {
+            raw: self.payload.clone(),
+        })
+    }
+}
+
+impl Bgx13p {
+    /**
+        Sends a command which isn't wrapped by this crate, e.g. one of a newer FW, and returns its answer.
+        The module is brought into command mode first. The response code is not checked, see [`RawResponse::is_success`].
+        Commands which may change the mode or the connection leave the state unknown, like any command this crate doesn't know.
+        Settings the host port has to follow are rejected, see [`InvalidCommand::HostSetting`].
+    */
+    pub fn raw_command(&mut self, line: &str, timeout: Duration) -> Result<RawResponse> {
+        let line = line.trim();
+        if line.is_empty() || line.contains(['\r', '\n']) {
+            return Err(InvalidCommand::Argument(line.to_string()).into());
+        }
+        let command = line.parse::<BgxCommand>().ok();
+        if let Some(c) = &command {
+            c.to_bytes()?;
+        }
+
+        self.switch_to_command_mode()?;
+        self.write_line(line.as_bytes(), None)?;
+
+        let res = self.read_complete_raw_response(timeout);
+        let succeeded = matches!(&res, Ok((h, _)) if h.response_code == ResponseCodes::Success);
+        self.track_command(command.as_ref(), succeeded);
+
+        let (header, payload) = res?;
+        debug!("Raw command {line:?} answered with {header:?}: {payload:?}");
+
+        Ok(RawResponse { header, payload })
+    }
+}
+
+#[test]
+fn raw_command_1() {
+    use crate::recording::Traffic;
+
+    let mut bgx = crate::recording::replay_opened([
+        Traffic::Sent(b"gfu 1 none\r\n".to_vec()),
+        Traffic::Received(b"R000009\r\nSuccess\r\n".to_vec()),
+        // the command isn't known, so the mode is checked again
+        Traffic::ReadTimeouts(1),
+        Traffic::Sent(b"\r\n".to_vec()),
+        Traffic::Sent(b"\r\n".to_vec()),
+        Traffic::Received(b"Ready\r\nReady\r\n".to_vec()),
+        Traffic::ReadTimeouts(1),
+        Traffic::Sent(b"gfu x\r\n".to_vec()),
+        Traffic::Received(b"R700".to_vec()),
+        Traffic::ReadTimeouts(2),
+    ]);
+
+    let res = bgx
+        .raw_command("gfu 1 none", Duration::from_secs(1))
+        .unwrap();
+    assert!(res.is_success());
+    assert_eq!(res.text().unwrap(), "Success\r\n");
+
+    // the bytes received so far are part of the error
+    let err = bgx
+        .raw_command("gfu x", crate::command::Command::TIMEOUT_COMMON)
+        .unwrap_err();
+    assert_eq!(
+        err.downcast_ref::<RawResponseError>(),
+        Some(&RawResponseError::Incomplete {
+            timeout: crate::command::Command::TIMEOUT_COMMON,
+            raw: b"R700".to_vec()
+        })
+    );
+}
+
+#[test]
+fn raw_command_2() {
+    use crate::{recording::Traffic, state::ModuleState};
+
+    let mut bgx = crate::recording::replay_opened([
+        Traffic::Sent(b"con d0cf5e828506 2\r\n".to_vec()),
+        Traffic::Received(b"R000009\r\nSuccess\r\n".to_vec()),
+    ]);
+
+    // the host port would have to follow, nothing is sent
+    let err = bgx
+        .raw_command("set ua b 9600", Duration::from_secs(1))
+        .unwrap_err();
+    assert_eq!(
+        err.downcast_ref::<InvalidCommand>(),
+        Some(&InvalidCommand::HostSetting("ua b".to_string()))
+    );
+
+    let res = bgx
+        .raw_command("con d0cf5e828506 2", Duration::from_secs(1))
+        .unwrap();
+    assert!(res.is_success());
+    assert_eq!(
+        bgx.state(),
+        ModuleState::Connected("d0cf5e828506".parse().unwrap())
+    );
+}
@@ -0,0 +1,543 @@
+use anyhow::{anyhow, Context, Error, Result};
+use log::warn;
+use serialport::{ClearBuffer, DataBits, FlowControl, Parity, SerialPort, StopBits};
+use std::{
+    collections::VecDeque,
+    fmt::Display,
+    fs::File,
+    io::{self, BufWriter, ErrorKind, Read, Write},
+    path::Path,
+    str::FromStr,
+    time::{Duration, Instant},
+};
+
+use crate::{bgx::Bgx13p, command::Command};
+
+/// what happened on the port
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Traffic {
+    Sent(Vec<u8>),
+    Received(Vec<u8>),
+    /// the given number of reads in a row returned without data
+    ReadTimeouts(u32),
+}
+
+/**
+    One line of a recording, time since the port was opened in microseconds, direction and data as hex:
+    ```text
+    1520 tx 7665720d0a
+    1873 timeout 3
+    31990 rx 52303030303239...
+    ```
+*/
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Record {
+    pub at: Duration,
+    pub traffic: Traffic,
+}
+
+impl Display for Record {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} ", self.at.as_micros())?;
+
+        let (direction, data) = match &self.traffic {
+            Traffic::Sent(d) => ("tx", d),
+            Traffic::Received(d) => ("rx", d),
+            Traffic::ReadTimeouts(n) => return write!(f, "timeout {n}"),
+        };
+        write!(f, "{direction} ")?;
+        for b in data {
+            write!(f, "{b:02x}")?;
+        }
+
+        Ok(())
+    }
+}
+
+impl FromStr for Record {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.split_whitespace();
+        let at = parts.next().context("Missing timestamp")?.parse()?;
+
+        let traffic = match (parts.next(), parts.next().unwrap_or_default()) {
+            (Some("tx"), data) => Traffic::Sent(parse_hex(data)?),
+            (Some("rx"), data) => Traffic::Received(parse_hex(data)?),
+            (Some("timeout"), n) => Traffic::ReadTimeouts(n.parse()?),
+            (d, _) => return Err(anyhow!("Unknown direction {d:?}")),
+        };
+
+        Ok(Record {
+            at: Duration::from_micros(at),
+            traffic,
+        })
+    }
+}
+
+fn parse_hex(data: &str) -> Result<Vec<u8>> {
+    if !data.len().is_multiple_of(2) || !data.is_ascii() {
+        return Err(anyhow!("Invalid hex data {data:?}"));
+    }
+
+    (0..data.len())
+        .step_by(2)
+        .map(|i| {
+            let byte = data.get(i..i + 2).unwrap_or_default();
+            u8::from_str_radix(byte, 16).with_context(|| format!("Invalid hex byte {byte:?}"))
+        })
+        .collect()
+}
+
+/// reads all records of a recording file
+pub fn load_recording(path: impl AsRef<Path>) -> Result<Vec<Record>> {
+    let path = path.as_ref();
+    std::fs::read_to_string(path)
+        .with_context(|| format!("Couldn't read recording {}", path.display()))?
+        .lines()
+        .enumerate()
+        .filter(|(_, l)| !l.trim().is_empty())
+        .map(|(i, l)| {
+            l.parse()
+                .with_context(|| format!("Line {} of recording", i + 1))
+        })
+        .collect()
+}
+
+/// wraps a port and writes every byte sent and received with a timestamp to the output
+pub struct RecordingPort {
+    inner: Box<dyn SerialPort>,
+    out: Box<dyn Write + Send>,
+    start: Instant,
+    // timeouts in a row are written as one record as soon as something else happens
+    pending_timeouts: Option<Record>,
+}
+
+impl RecordingPort {
+    pub fn new(inner: Box<dyn SerialPort>, out: impl Write + Send + 'static) -> Self {
+        Self {
+            inner,
+            out: Box::new(out),
+            start: Instant::now(),
+            pending_timeouts: None,
+        }
+    }
+
+    fn record(&mut self, traffic: Traffic) -> io::Result<()> {
+        let at = self.start.elapsed();
+
+        if let Traffic::ReadTimeouts(_) = traffic {
+            match &mut self.pending_timeouts {
+                Some(Record {
+                    traffic: Traffic::ReadTimeouts(n),
+                    ..
+                }) => *n += 1,
+                _ => self.pending_timeouts = Some(Record { at, traffic }),
+            }
+            return Ok(());
+        }
+
+        self.write_pending_timeouts()?;
+        writeln!(self.out, "{}", Record { at, traffic })?;
+        // flushed right away so that nothing is lost if the process dies
+        self.out.flush()
+    }
+
+    fn write_pending_timeouts(&mut self) -> io::Result<()> {
+        if let Some(r) = self.pending_timeouts.take() {
+            writeln!(self.out, "{r}")?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Drop for RecordingPort {
+    fn drop(&mut self) {
+        if let Err(e) = self.write_pending_timeouts().and_then(|_| self.out.flush()) {
+            warn!("Couldn't finish recording: {e}");
+        }
+    }
+}
+
+impl Read for RecordingPort {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self.inner.read(buf) {
+            Ok(n) => {
+                if let Some(data) = buf.get(..n).filter(|d| !d.is_empty()) {
+                    self.record(Traffic::Received(data.to_vec()))?;
+                }
+                Ok(n)
+            }
+            Err(e) if e.kind() == ErrorKind::TimedOut => {
+                self.record(Traffic::ReadTimeouts(1))?;
+                Err(e)
+            }
+            Err(e) => Err(e),
+        }
+    }
+}
+
+impl Write for RecordingPort {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        self.record(Traffic::Sent(buf.get(..n).unwrap_or_default().to_vec()))?;
+
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+impl SerialPort for RecordingPort {
+    fn name(&self) -> Option<String> {
+        self.inner.name()
+    }
+    fn baud_rate(&self) -> serialport::Result<u32> {
+        self.inner.baud_rate()
+    }
+    fn data_bits(&self) -> serialport::Result<DataBits> {
+        self.inner.data_bits()
+    }
+    fn flow_control(&self) -> serialport::Result<FlowControl> {
+        self.inner.flow_control()
+    }
+    fn parity(&self) -> serialport::Result<Parity> {
+        self.inner.parity()
+    }
+    fn stop_bits(&self) -> serialport::Result<StopBits> {
+        self.inner.stop_bits()
+    }
+    fn timeout(&self) -> Duration {
+        self.inner.timeout()
+    }
+    fn set_baud_rate(&mut self, baud_rate: u32) -> serialport::Result<()> {
+        self.inner.set_baud_rate(baud_rate)
+    }
+    fn set_data_bits(&mut self, data_bits: DataBits) -> serialport::Result<()> {
+        self.inner.set_data_bits(data_bits)
+    }
+    fn set_flow_control(&mut self, flow_control: FlowControl) -> serialport::Result<()> {
+        self.inner.set_flow_control(flow_control)
+    }
+    fn set_parity(&mut self, parity: Parity) -> serialport::Result<()> {
+        self.inner.set_parity(parity)
+    }
+    fn set_stop_bits(&mut self, stop_bits: StopBits) -> serialport::Result<()> {
+        self.inner.set_stop_bits(stop_bits)
+    }
+    fn set_timeout(&mut self, timeout: Duration) -> serialport::Result<()> {
+        self.inner.set_timeout(timeout)
+    }
+    fn write_request_to_send(&mut self, level: bool) -> serialport::Result<()> {
+        self.inner.write_request_to_send(level)
+    }
+    fn write_data_terminal_ready(&mut self, level: bool) -> serialport::Result<()> {
+        self.inner.write_data_terminal_ready(level)
+    }
+    fn read_clear_to_send(&mut self) -> serialport::Result<bool> {
+        self.inner.read_clear_to_send()
+    }
+    fn read_data_set_ready(&mut self) -> serialport::Result<bool> {
+        self.inner.read_data_set_ready()
+    }
+    fn read_ring_indicator(&mut self) -> serialport::Result<bool> {
+        self.inner.read_ring_indicator()
+    }
+    fn read_carrier_detect(&mut self) -> serialport::Result<bool> {
+        self.inner.read_carrier_detect()
+    }
+    fn bytes_to_read(&self) -> serialport::Result<u32> {
+        self.inner.bytes_to_read()
+    }
+    fn bytes_to_write(&self) -> serialport::Result<u32> {
+        self.inner.bytes_to_write()
+    }
+    fn clear(&self, buffer_to_clear: ClearBuffer) -> serialport::Result<()> {
+        self.inner.clear(buffer_to_clear)
+    }
+    fn try_clone(&self) -> serialport::Result<Box<dyn SerialPort>> {
+        Err(unsupported("Recording ports can't be cloned"))
+    }
+    fn set_break(&self) -> serialport::Result<()> {
+        self.inner.set_break()
+    }
+    fn clear_break(&self) -> serialport::Result<()> {
+        self.inner.clear_break()
+    }
+}
+
+/**
+    Plays a recording back as port without any hardware.
+    Received data and timeouts are returned in the recorded order, sent data has to match the recording,
+    a divergence is reported as `InvalidData` error.
+*/
+pub struct ReplayPort {
+    traffic: VecDeque<Traffic>,
+    baud_rate: u32,
+    flow_control: FlowControl,
+    timeout: Duration,
+}
+
+impl ReplayPort {
+    pub fn new(records: Vec<Record>) -> Self {
+        Self {
+            traffic: records.into_iter().map(|r| r.traffic).collect(),
+            baud_rate: Bgx13p::DEFAULT_BAUD_RATE,
+            flow_control: FlowControl::None,
+            timeout: Command::TIMEOUT_COMMON,
+        }
+    }
+}
+
+impl Read for ReplayPort {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self.traffic.front_mut() {
+            Some(Traffic::Received(data)) => {
+                let n = data.len().min(buf.len());
+                for (b, d) in buf.iter_mut().zip(data.drain(..n)) {
+                    *b = d;
+                }
+                if data.is_empty() {
+                    self.traffic.pop_front();
+                }
+                Ok(n)
+            }
+            Some(Traffic::ReadTimeouts(n)) => {
+                *n = n.saturating_sub(1);
+                if *n == 0 {
+                    self.traffic.pop_front();
+                }
+                Err(ErrorKind::TimedOut.into())
+            }
+            // the host is expected to send first or the recording is over
+            _ => Err(ErrorKind::TimedOut.into()),
+        }
+    }
+}
+
+impl Write for ReplayPort {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        // the number of timeouts depends on the timing of the host, so surplus ones are skipped
+        while let Some(Traffic::ReadTimeouts(_)) = self.traffic.front() {
+            self.traffic.pop_front();
+        }
+
+        let diverged = |expected: &dyn std::fmt::Debug| {
+            io::Error::new(
+                ErrorKind::InvalidData,
+                format!("Replay diverged, expected {expected:?} but {buf:?} has been sent"),
+            )
+        };
+
+        match self.traffic.front_mut() {
+            Some(Traffic::Sent(expected)) => {
+                // a recorded write may be split or merged differently by the host
+                let n = expected.len().min(buf.len());
+                if expected.get(..n) != buf.get(..n) {
+                    return Err(diverged(expected));
+                }
+
+                expected.drain(..n);
+                if expected.is_empty() {
+                    self.traffic.pop_front();
+                }
+                Ok(n)
+            }
+            other => Err(diverged(&other)),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl SerialPort for ReplayPort {
+    fn name(&self) -> Option<String> {
+        Some("replay".to_string())
+    }
+    fn baud_rate(&self) -> serialport::Result<u32> {
+        Ok(self.baud_rate)
+    }
+    fn data_bits(&self) -> serialport::Result<DataBits> {
+        Ok(DataBits::Eight)
+    }
+    fn flow_control(&self) -> serialport::Result<FlowControl> {
+        Ok(self.flow_control)
+    }
+    fn parity(&self) -> serialport::Result<Parity> {
+        Ok(Parity::None)
+    }
+    fn stop_bits(&self) -> serialport::Result<StopBits> {
+        Ok(StopBits::One)
+    }
+    fn timeout(&self) -> Duration {
+        self.timeout
+    }
+    fn set_baud_rate(&mut self, baud_rate: u32) -> serialport::Result<()> {
+        self.baud_rate = baud_rate;
+        Ok(())
+    }
+    fn set_data_bits(&mut self, _data_bits: DataBits) -> serialport::Result<()> {
+        Ok(())
+    }
+    fn set_flow_control(&mut self, flow_control: FlowControl) -> serialport::Result<()> {
+        self.flow_control = flow_control;
+        Ok(())
+    }
+    fn set_parity(&mut self, _parity: Parity) -> serialport::Result<()> {
+        Ok(())
+    }
+    fn set_stop_bits(&mut self, _stop_bits: StopBits) -> serialport::Result<()> {
+        Ok(())
+    }
+    fn set_timeout(&mut self, timeout: Duration) -> serialport::Result<()> {
+        self.timeout = timeout;
+        Ok(())
+    }
+    fn write_request_to_send(&mut self, _level: bool) -> serialport::Result<()> {
+        Ok(())
+    }
+    fn write_data_terminal_ready(&mut self, _level: bool) -> serialport::Result<()> {
+        Ok(())
+    }
+    fn read_clear_to_send(&mut self) -> serialport::Result<bool> {
+        Ok(true)
+    }
+    fn read_data_set_ready(&mut self) -> serialport::Result<bool> {
+        Ok(true)
+    }
+    fn read_ring_indicator(&mut self) -> serialport::Result<bool> {
+        Ok(false)
+    }
+    fn read_carrier_detect(&mut self) -> serialport::Result<bool> {
+        Ok(true)
+    }
+    fn bytes_to_read(&self) -> serialport::Result<u32> {
+        Ok(match self.traffic.front() {
+            Some(Traffic::Received(d)) => u32::try_from(d.len()).unwrap_or(u32::MAX),
+            _ => 0,
+        })
+    }
+    fn bytes_to_write(&self) -> serialport::Result<u32> {
+        Ok(0)
+    }
+    // the recording only contains what has been read after clearing
+    fn clear(&self, _buffer_to_clear: ClearBuffer) -> serialport::Result<()> {
+        Ok(())
+    }
+    fn try_clone(&self) -> serialport::Result<Box<dyn SerialPort>> {
+        Err(unsupported("Replay ports can't be cloned"))
+    }
+    fn set_break(&self) -> serialport::Result<()> {
+        Ok(())
+    }
+    fn clear_break(&self) -> serialport::Result<()> {
+        Ok(())
+    }
+}
+
+fn unsupported(description: &str) -> serialport::Error {
+    serialport::Error::new(
+        serialport::ErrorKind::Io(ErrorKind::Unsupported),
+        description,
+    )
+}
+
+impl Bgx13p {
+    /// opens the port like [`Bgx13p::open`] and records all traffic into the given file
+    pub fn open_recorded(port_name: &str, recording: impl AsRef<Path>) -> Result<Self> {
+        let recording = recording.as_ref();
+        let out = File::create(recording)
+            .with_context(|| format!("Couldn't create recording {}", recording.display()))?;
+
+        Self::open_with_port(Box::new(RecordingPort::new(
+            Self::open_port(port_name)?,
+            BufWriter::new(out),
+        )))
+    }
+
+    /// replays a recording made with [`Bgx13p::open_recorded`], the same calls have to be made as back then
+    pub fn open_replay(recording: impl AsRef<Path>) -> Result<Self> {
+        Self::open_with_port(Box::new(ReplayPort::new(load_recording(recording)?)))
+    }
+}
+
+#[test]
+fn record_parse_1() {
+    let records = [
+        Record {
+            at: Duration::from_micros(1520),
+            traffic: Traffic::Sent(b"ver\r\n".to_vec()),
+        },
+        Record {
+            at: Duration::from_micros(1873),
+            traffic: Traffic::ReadTimeouts(3),
+        },
+        Record {
+            at: Duration::from_micros(31990),
+            traffic: Traffic::Received(vec![0x00, 0xff, b'R']),
+        },
+    ];
+    let lines = ["1520 tx 7665720d0a", "1873 timeout 3", "31990 rx 00ff52"];
+
+    for (r, l) in records.iter().zip(lines) {
+        assert_eq!(r.to_string(), l);
+        assert_eq!(&l.parse::<Record>().unwrap(), r);
+    }
+
+    assert!("1 tx 7".parse::<Record>().is_err());
+    assert!("1 up 00".parse::<Record>().is_err());
+}
+
+/// module opened on a replay which starts with the traffic of opening it, followed by the given traffic
+#[cfg(test)]
+pub(crate) fn replay_opened(traffic: impl IntoIterator<Item = Traffic>) -> Bgx13p {
+    let opening = [
+        // probing the command mode
+        Traffic::ReadTimeouts(1),
+        Traffic::Sent(b"\r\n".to_vec()),
+        Traffic::Sent(b"\r\n".to_vec()),
+        Traffic::Received(b"Ready\r\nReady\r\n".to_vec()),
+        Traffic::ReadTimeouts(1),
+        // firmware version
+        Traffic::Sent(b"ver\r\n".to_vec()),
+        Traffic::Received(b"R000029\r\nBGX13P.1.2.2738.2-1524-2738\r\n".to_vec()),
+        Traffic::ReadTimeouts(1),
+        // flow control of the module
+        Traffic::Sent(b"get ua f\r\n".to_vec()),
+        Traffic::Received(b"R000005\r\noff\r\n".to_vec()),
+    ];
+
+    Bgx13p::open_with_port(replay(opening.into_iter().chain(traffic))).unwrap()
+}
+
+/// port which replays the given traffic, timing doesn't matter
+#[cfg(test)]
+pub(crate) fn replay(traffic: impl IntoIterator<Item = Traffic>) -> Box<dyn SerialPort> {
+    let records = traffic
+        .into_iter()
+        .map(|traffic| Record {
+            at: Duration::ZERO,
+            traffic,
+        })
+        .collect();
+
+    Box::new(ReplayPort::new(records))
+}
+
+#[test]
+fn replay_1() {
+    let mut bgx = replay_opened([
+        Traffic::Sent(b"ver\r\n".to_vec()),
+        Traffic::Received(b"R000029\r\nBGX13P.1.2.2738.2-1524-2738\r\n".to_vec()),
+        Traffic::ReadTimeouts(1),
+    ]);
+    assert_eq!(bgx.fw_version().unwrap(), "BGX13P.1.2.2738.2-1524-2738");
+
+    // nothing is left to replay
+    assert!(bgx.write_all_with_timeout(b"dct\r\n", None).is_err());
+}
@@ -0,0 +1,147 @@
+use anyhow::{anyhow, Context, Result};
+use log::{debug, info, warn};
+
+use crate::{
+    bgx::Bgx13p,
+    command::BgxCommand,
+    con_param::ConInfo,
+    mac::Mac,
+    response::{BgxResponse, ResponseCodes},
+    state::ModuleState,
+};
+
+/**
+    Handle to send commands to the peer module of an active connection.
+    The peer must allow remote commanding, see [`Bgx13p::set_remote_commanding`].
+    The local module is brought back to stream mode when the handle is dropped.
+*/
+pub struct RemoteBgx<'a> {
+    bgx: &'a mut Bgx13p,
+    peer: Mac,
+}
+
+impl Bgx13p {
+    /// switches into remote command mode, requires an active connection to a peer module
+    pub fn remote(&mut self) -> Result<RemoteBgx<'_>> {
+        self.switch_to_command_mode()?;
+
+        let ConInfo(peer) = self
+            .con_info()?
+            .context("Remote command mode needs an active connection")?;
+        info!("Enter remote command mode of peer {peer}");
+
+        self.write_expect_success(&BgxCommand::RemoteCommandMode, None)?;
+        // local commands need the break sequence again from now on
+        self.state = ModuleState::Remote(peer);
+
+        Ok(RemoteBgx { bgx: self, peer })
+    }
+
+    /// allows or forbids a connected central to command this module, setting is saved
+    pub fn set_remote_commanding(&mut self, enabled: bool) -> Result<()> {
+        self.switch_to_command_mode()?;
+
+        self.write_expect_success(
+            &BgxCommand::SetRemoteCommanding(enabled),
+            self.timeouts.settings,
+        )?;
+        self.write_expect_success(&BgxCommand::Save, self.timeouts.settings)
+    }
+}
+
+impl RemoteBgx<'_> {
+    /// sends a command to the peer module and returns the data of its answer
+    pub fn command(&mut self, cmd: &str) -> Result<String> {
+        self.bgx
+            .write_line(cmd.as_bytes(), self.bgx.timeouts.remote)?;
+
+        match self.bgx.read_bgx_response(self.bgx.timeouts.remote)? {
+            BgxResponse::DataWithHeader(h, ans) if h.response_code == ResponseCodes::Success => {
+                debug!("Remote answered {:?} on {:?}", ans, cmd);
+                Ok(ans)
+            }
+            BgxResponse::DataWithHeader(h, ans) => Err(anyhow::Error::new(h.response_code)
+                .context(format!("Remote command {cmd:?} failed with {ans:?}"))),
+            BgxResponse::DataWithoutHeader(d) => Err(anyhow!(
+                "Remote answered without header on {:?}: {:?}",
+                cmd,
+                d
+            )),
+        }
+    }
+
+    /// firmware version string of the peer module
+    pub fn version(&mut self) -> Result<String> {
+        let ver = self.command(&BgxCommand::Version.to_string())?;
+
+        Ok(ver.trim().to_string())
+    }
+
+    /// device name of the peer module
+    pub fn name(&mut self) -> Result<String> {
+        self.get("sy d n")
+    }
+
+    /// reads a configuration variable of the peer module, e.g. `sy d n`
+    pub fn get(&mut self, variable: &str) -> Result<String> {
+        let value = self.command(&format!("get {variable}"))?;
+
+        Ok(value.trim().to_string())
+    }
+}
+
+impl Drop for RemoteBgx<'_> {
+    fn drop(&mut self) {
+        let res = self
+            .bgx
+            .switch_to_command_mode()
+            .and_then(|_| self.bgx.enter_stream_mode(self.peer));
+
+        if let Err(e) = res {
+            warn!("Couldn't leave remote command mode: {e}");
+        }
+    }
+}
+
+#[test]
+fn remote_1() {
+    use crate::recording::Traffic;
+
+    let mut bgx = crate::recording::replay_opened([
+        Traffic::Sent(b"con params\r\n".to_vec()),
+        Traffic::Received(b"R000039\r\n!  Param Value\r\n#  Addr  d0cf5e828506\r\n".to_vec()),
+        Traffic::ReadTimeouts(1),
+        Traffic::Sent(b"rmt\r\n".to_vec()),
+        Traffic::Received(b"R000000\r\n".to_vec()),
+        Traffic::ReadTimeouts(1),
+        // answered by the peer
+        Traffic::Sent(b"get sy d n\r\n".to_vec()),
+        Traffic::Received(b"R000012\r\nJugglerBGX\r\n".to_vec()),
+        Traffic::ReadTimeouts(1),
+        Traffic::Sent(b"get sy x\r\n".to_vec()),
+        Traffic::Received(b"R600000\r\n".to_vec()),
+        Traffic::ReadTimeouts(1),
+        // leaving the remote command mode
+        Traffic::Sent(b"$$$".to_vec()),
+        Traffic::ReadTimeouts(1),
+        Traffic::Sent(b"\r\n\r\n".to_vec()),
+        Traffic::Received(b"Ready\r\n".to_vec()),
+        Traffic::ReadTimeouts(1),
+        Traffic::Sent(b"str\r\n".to_vec()),
+        Traffic::Received(b"R000000\r\n".to_vec()),
+        Traffic::ReadTimeouts(1),
+    ]);
+    let peer = "d0cf5e828506".parse().unwrap();
+
+    {
+        let mut remote = bgx.remote().unwrap();
+        assert_eq!(remote.name().unwrap(), "JugglerBGX");
+        assert!(remote.get("sy x").is_err());
+        // answers of the peer carry headers but the local module is still in remote command mode
+        assert_eq!(remote.bgx.state(), ModuleState::Remote(peer));
+    }
+
+    assert_eq!(bgx.state(), ModuleState::Connected(peer));
+    // nothing else has been sent
+    assert!(bgx.write_all_with_timeout(b"\r\n", None).is_err());
+}
@@ -0,0 +1,147 @@
+use anyhow::{anyhow, Result};
+use log::{debug, info};
+use std::time::Instant;
+
+use crate::{bgx::Bgx13p, command::BgxCommand, fw::parse_fw_ver, mac::Mac, state::ModuleState};
+
+impl Bgx13p {
+    /// reboots the module, waits until it booted and brings it into command mode
+    pub fn reboot(&mut self) -> Result<()> {
+        self.switch_to_command_mode()?;
+
+        self.clear_received()?;
+        self.write_command(&BgxCommand::Reboot, None)?;
+        self.wait_for_boot()?;
+
+        self.switch_to_command_mode()
+    }
+
+    /**
+        Resets all settings of the module to factory defaults and applies the default settings afterwards.
+        The own address of the module has to be given as confirmation.
+        The host port follows the module back to the default baud rate without flow control.
+    */
+    pub fn factory_reset(&mut self, own_address: &Mac) -> Result<()> {
+        self.switch_to_command_mode()?;
+
+        self.clear_received()?;
+        self.write_command(&BgxCommand::FactoryReset(*own_address), None)?;
+
+        // the module boots with the default UART settings and without the old settings, the banner is kept
+        self.set_host_baud_rate(Self::DEFAULT_BAUD_RATE)?;
+        self.set_host_flow_control(false)?;
+        self.device_info = None;
+        self.default_settings_applied = false;
+
+        self.wait_for_boot()?;
+        self.reach_well_known_state()
+    }
+
+    /// waits for the boot banner which contains the FW version
+    pub(crate) fn wait_for_boot(&mut self) -> Result<()> {
+        self.state = ModuleState::Unknown;
+        self.advertising = false;
+
+        let booted = |b: &[u8]| parse_fw_ver(&String::from_utf8_lossy(b)).is_ok();
+        let mut banner = Vec::new();
+        self.read_until(Instant::now() + self.timeouts.boot, &mut banner, booted)?;
+
+        let banner = String::from_utf8_lossy(&banner);
+        debug!("Boot banner: {:?}", banner);
+
+        let (_, fw_version) = parse_fw_ver(&banner)
+            .map_err(|e| anyhow!("Module didn't boot within {:?}: {e:?}", self.timeouts.boot))?;
+        info!("Module {fw_version} booted");
+
+        Ok(())
+    }
+}
+
+/// answer with header to a command
+#[cfg(test)]
+fn exchange(sent: &str, received: &str) -> [crate::recording::Traffic; 2] {
+    use crate::recording::Traffic;
+
+    [
+        Traffic::Sent(format!("{sent}\r\n").into_bytes()),
+        Traffic::Received(format!("R{:06}\r\n{received}", received.len()).into_bytes()),
+    ]
+}
+
+/// probe after the boot, the module answers right away
+#[cfg(test)]
+fn probe() -> [crate::recording::Traffic; 3] {
+    use crate::recording::Traffic;
+
+    [
+        Traffic::Sent(b"\r\n\r\n".to_vec()),
+        Traffic::Received(b"Ready\r\n".to_vec()),
+        Traffic::ReadTimeouts(1),
+    ]
+}
+
+#[test]
+fn reboot_1() {
+    use crate::recording::Traffic;
+
+    let traffic = [
+        vec![
+            Traffic::Sent(b"reboot\r\n".to_vec()),
+            Traffic::Received(b"R000000\r\n\r\nBGX13P.1.2.2738.2-1524-2738\r\n".to_vec()),
+            Traffic::ReadTimeouts(1),
+        ],
+        probe().to_vec(),
+    ];
+    let mut bgx = crate::recording::replay_opened(traffic.into_iter().flatten());
+
+    bgx.reboot().unwrap();
+    assert_eq!(bgx.state(), ModuleState::Command);
+}
+
+#[test]
+fn factory_reset_1() {
+    use crate::recording::Traffic;
+    use serialport::FlowControl;
+
+    let mac: Mac = "d0cf5e828506".parse().unwrap();
+    let traffic = [
+        exchange("ver", "BGX13P.1.2.2738.2-1524-2738\r\n").to_vec(),
+        exchange("get bl a", "d0cf5e828506\r\n").to_vec(),
+        exchange("get sy b v", "1.2.2738\r\n").to_vec(),
+        exchange("get sy u", "0123\r\n").to_vec(),
+        // the banner arrives with the default rate
+        vec![
+            Traffic::Sent(b"fac d0cf5e828506\r\n".to_vec()),
+            Traffic::Received(b"R000000\r\n\r\nBGX13P.1.2.2738.2-1524-2738\r\n".to_vec()),
+            Traffic::ReadTimeouts(1),
+        ],
+        probe().to_vec(),
+        exchange("ver", "BGX13P.1.2.2738.2-1524-2738\r\n").to_vec(),
+        exchange("get sy r e", "0\r\n").to_vec(),
+        exchange("get bl v h d", "0\r\n").to_vec(),
+        exchange("get bl e p", "any\r\n").to_vec(),
+        exchange("get bl p m", "0\r\n").to_vec(),
+        exchange("get bl p p", "1M\r\n").to_vec(),
+        exchange("get sy d n", "BGX-8506\r\n").to_vec(),
+        exchange("set sy d n JugglerBGX", "").to_vec(),
+        exchange("save", "").to_vec(),
+        exchange("", "").to_vec(),
+        // the device info is read again
+        exchange("ver", "BGX13P.1.2.2738.2-1524-2738\r\n").to_vec(),
+        exchange("get bl a", "d0cf5e828506\r\n").to_vec(),
+        exchange("get sy b v", "1.2.2738\r\n").to_vec(),
+        exchange("get sy u", "4567\r\n").to_vec(),
+    ];
+    let mut bgx = crate::recording::replay_opened(traffic.into_iter().flatten());
+    bgx.set_host_baud_rate(9600).unwrap();
+    bgx.set_host_flow_control(true).unwrap();
+    bgx.state = ModuleState::Command;
+    bgx.device_info().unwrap();
+    bgx.default_settings_applied = true;
+
+    bgx.factory_reset(&mac).unwrap();
+    assert_eq!(bgx.port.baud_rate().unwrap(), Bgx13p::DEFAULT_BAUD_RATE);
+    assert_eq!(bgx.port.flow_control().unwrap(), FlowControl::None);
+    assert!(bgx.default_settings_applied);
+    assert_eq!(bgx.device_info.unwrap().uuid.as_deref(), Some("4567"));
+}
@@ -96,6 +96,72 @@ pub fn parse_response(input: &[u8]) -> IResult<&[u8], BgxResponse> {
     Ok((input, BgxResponse::DataWithHeader(header, answer)))
 }
 
+/// length of a complete header like R000029\r\n
+const HEADER_LEN: usize = 9;
+
+/**
+    Splits the first complete response or the data in front of it off the input
+    and returns it together with the number of consumed bytes.
+    Returns None if more input is needed, i.e. for an incomplete response or a header which may be incomplete.
+    With `at_end` no more input follows, so what only looks like the start of a header is taken as data.
+*/
+pub fn parse_next_response(input: &[u8], at_end: bool) -> Option<(BgxResponse, usize)> {
+    if input.is_empty() {
+        return None;
+    }
+
+    match find_header(input) {
+        // everything in front of a header is returned on its own
+        Some((i, ..)) if i > 0 => {
+            Some((BgxResponse::DataWithoutHeader(input.get(..i)?.to_vec()), i))
+        }
+        Some((_, h, body)) => {
+            let len = usize::try_from(h.data_length).ok()?;
+            // the body may still be on its way
+            let answer = body.get(..len)?;
+            let answer = match String::from_utf8(answer.to_vec()) {
+                Ok(answer) => answer,
+                Err(_) => format!("{answer:?}"),
+            };
+
+            Some((
+                BgxResponse::DataWithHeader(h, answer),
+                input.len() - body.len() + len,
+            ))
+        }
+        None => {
+            let start = input.len().saturating_sub(HEADER_LEN - 1);
+            let partial_header = (start..input.len())
+                .find(|&i| input.get(i..).is_some_and(is_header_start))
+                .filter(|_| !at_end);
+
+            match partial_header {
+                Some(0) => None,
+                Some(i) => Some((BgxResponse::DataWithoutHeader(input.get(..i)?.to_vec()), i)),
+                None => Some((BgxResponse::DataWithoutHeader(input.to_vec()), input.len())),
+            }
+        }
+    }
+}
+
+/// finds the first complete header and returns its offset, the header and everything following it
+pub(crate) fn find_header(input: &[u8]) -> Option<(usize, ResponseHeader, &[u8])> {
+    (0..input.len()).find_map(|i| {
+        let rest = input.get(i..)?;
+        parse_header(rest).ok().map(|(body, h)| (i, h, body))
+    })
+}
+
+/// whether the input could be the beginning of a header
+fn is_header_start(input: &[u8]) -> bool {
+    input.len() < HEADER_LEN
+        && input.iter().enumerate().all(|(i, b)| match i {
+            0 => *b == b'R',
+            1..=6 => b.is_ascii_digit(),
+            _ => *b == b'\r',
+        })
+}
+
 #[test]
 fn module_response_test_1() {
     const input1: &[u8] = b"R000029\r\nBGX13P.1.2.2738.2-1524-2738\r\n";
@@ -153,3 +219,46 @@ fn module_response_test_2() {
         parse_response(input).unwrap().1
     )
 }
+
+#[test]
+fn parse_next_response_1() {
+    const INPUT: &[u8] = b"ab R000002\r\nokR000005\r\nend";
+    let ok = BgxResponse::DataWithHeader(
+        ResponseHeader {
+            response_code: ResponseCodes::Success,
+            data_length: 2,
+        },
+        "ok".to_string(),
+    );
+
+    // data in front of the header, then the response, the last one is incomplete
+    assert_eq!(
+        parse_next_response(INPUT, false),
+        Some((BgxResponse::DataWithoutHeader(b"ab ".to_vec()), 3))
+    );
+    assert_eq!(
+        parse_next_response(INPUT.get(3..).unwrap(), false),
+        Some((ok, 11))
+    );
+    assert_eq!(parse_next_response(INPUT.get(14..).unwrap(), false), None);
+    assert_eq!(parse_next_response(INPUT.get(14..).unwrap(), true), None);
+    assert_eq!(parse_next_response(b"", true), None);
+}
+
+#[test]
+fn parse_next_response_2() {
+    // a possibly incomplete header is kept back unless no more input follows
+    assert_eq!(
+        parse_next_response(b"data R0000", false),
+        Some((BgxResponse::DataWithoutHeader(b"data ".to_vec()), 5))
+    );
+    assert_eq!(parse_next_response(b"R0000", false), None);
+    assert_eq!(
+        parse_next_response(b"R0000", true),
+        Some((BgxResponse::DataWithoutHeader(b"R0000".to_vec()), 5))
+    );
+    assert_eq!(
+        parse_next_response(b"ERROR", false),
+        Some((BgxResponse::DataWithoutHeader(b"ERRO".to_vec()), 4))
+    );
+}
@@ -0,0 +1,319 @@
+use std::{fmt::Display, str::FromStr};
+
+use anyhow::{anyhow, Context, Error, Result};
+use log::{debug, info, warn};
+use thiserror::Error;
+
+use crate::{bgx::Bgx13p, command::BgxCommand, response::ResponseCodes};
+
+/// a single setting which has been rejected by the module
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct SettingFailure {
+    pub setting: String,
+    pub code: ResponseCodes,
+}
+
+impl Display for SettingFailure {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?} -> {}", self.setting, self.code)
+    }
+}
+
+/// a variable which doesn't have the value it has been set to
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct SettingMismatch {
+    pub variable: String,
+    pub expected: String,
+    pub actual: String,
+}
+
+impl Display for SettingMismatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{:?} is {:?} instead of {:?}",
+            self.variable, self.actual, self.expected
+        )
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Error)]
+pub enum SettingsError {
+    #[error("Module rejected {} setting(s): {}", .0.len(), .0.iter().map(ToString::to_string).collect::<Vec<_>>().join(", "))]
+    Rejected(Vec<SettingFailure>),
+    #[error("Module didn't keep {} setting(s): {}", .0.len(), .0.iter().map(ToString::to_string).collect::<Vec<_>>().join(", "))]
+    Mismatch(Vec<SettingMismatch>),
+}
+
+/**
+    Configuration variables with their values, written as text one per line as `variable = value`:
+    ```text
+    # comments and empty lines are ignored
+    sy d n = JugglerBGX
+    bl e p = any
+    ```
+*/
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct Profile(pub Vec<(String, String)>);
+
+impl FromStr for Profile {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.lines()
+            .map(str::trim)
+            .enumerate()
+            .filter(|(_, l)| !l.is_empty() && !l.starts_with('#'))
+            .map(|(i, l)| {
+                let (variable, value) = l
+                    .split_once('=')
+                    .with_context(|| format!("Missing '=' in line {}: {l:?}", i + 1))?;
+
+                match (variable.trim(), value.trim()) {
+                    ("", _) => Err(anyhow!("Missing variable in line {}: {l:?}", i + 1)),
+                    (variable, value) => Ok((variable.to_string(), value.to_string())),
+                }
+            })
+            .collect::<Result<_>>()
+            .map(Profile)
+    }
+}
+
+impl Display for Profile {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (variable, value) in &self.0 {
+            writeln!(f, "{variable} = {value}")?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Bgx13p {
+    /// reads a configuration variable, e.g. `sy d n`
+    pub fn get(&mut self, variable: &str) -> Result<String> {
+        self.switch_to_command_mode()?;
+
+        self.get_variable(variable)
+    }
+
+    /// sets a configuration variable without saving it, the protocol mode and the UART have their own methods
+    pub fn set(&mut self, variable: &str, value: &str) -> Result<()> {
+        let cmd = BgxCommand::set(variable, value);
+        cmd.to_bytes()?;
+        self.switch_to_command_mode()?;
+
+        self.write_expect_success(&cmd, self.timeouts.settings)
+    }
+
+    /// saves the current configuration to flash
+    pub fn save(&mut self) -> Result<()> {
+        self.switch_to_command_mode()?;
+
+        self.write_expect_success(&BgxCommand::Save, self.timeouts.settings)
+    }
+
+    /// sets all variables of the profile and saves them if the module accepted all of them
+    pub fn apply_profile(&mut self, profile: &Profile) -> Result<()> {
+        self.switch_to_command_mode()?;
+
+        let cmds = profile
+            .0
+            .iter()
+            .map(|(variable, value)| BgxCommand::set(variable, value))
+            .collect::<Vec<_>>();
+
+        self.apply_acknowledged(&cmds)
+    }
+
+    /**
+        Sets and saves only the variables whose current value differs from the profile and returns them.
+        Nothing is written if the module already matches, which spares the flash.
+    */
+    pub fn apply_profile_changes(&mut self, profile: &Profile) -> Result<Profile> {
+        let changes = Profile(
+            self.mismatches(profile)?
+                .into_iter()
+                .map(|m| (m.variable, m.expected))
+                .collect(),
+        );
+
+        if changes.0.is_empty() {
+            debug!("Configuration already matches the profile");
+        } else {
+            debug!("Apply changed variables: {changes:?}");
+            self.apply_profile(&changes)?;
+        }
+
+        Ok(changes)
+    }
+
+    /// reads the current values of all variables of the profile
+    pub fn read_profile(&mut self, profile: &Profile) -> Result<Profile> {
+        self.switch_to_command_mode()?;
+
+        profile
+            .0
+            .iter()
+            .map(|(variable, _)| Ok((variable.clone(), self.get_variable(variable)?)))
+            .collect::<Result<_>>()
+            .map(Profile)
+    }
+
+    /**
+        Applies the profile as a transaction: the current values are read first, then all variables are set,
+        read back and saved. With `reboot` the module is rebooted afterwards and the values are verified again.
+        If any step fails the previous values are restored and the error is returned.
+    */
+    pub fn apply_profile_verified(&mut self, profile: &Profile, reboot: bool) -> Result<()> {
+        let snapshot = self.read_profile(profile)?;
+        debug!("Configuration before transaction: {snapshot:?}");
+
+        // the flash only has to be restored once the new values may have been saved
+        let mut saved = false;
+        let res = self.set_verified(profile).and_then(|_| {
+            saved = true;
+            self.save()?;
+
+            if reboot {
+                self.reboot()?;
+                self.verify_profile(profile)?;
+            }
+
+            Ok(())
+        });
+
+        let Err(e) = res else {
+            info!("Configuration transaction succeeded");
+            return Ok(());
+        };
+        warn!("Configuration transaction failed, restore previous values: {e:#}");
+
+        match self.restore_profile(&snapshot, saved) {
+            Ok(()) => Err(e.context("Configuration failed, previous values restored")),
+            Err(restore_error) => Err(e.context(format!(
+                "Configuration failed and previous values couldn't be restored: {restore_error:#}"
+            ))),
+        }
+    }
+
+    fn set_verified(&mut self, profile: &Profile) -> Result<()> {
+        for (variable, value) in &profile.0 {
+            self.set(variable, value)?;
+        }
+
+        self.verify_profile(profile)
+    }
+
+    /// reads all variables back and compares them with the profile
+    fn verify_profile(&mut self, profile: &Profile) -> Result<()> {
+        let mismatches = self.mismatches(profile)?;
+
+        if !mismatches.is_empty() {
+            return Err(SettingsError::Mismatch(mismatches).into());
+        }
+
+        Ok(())
+    }
+
+    /// variables whose current value differs from the profile
+    fn mismatches(&mut self, profile: &Profile) -> Result<Vec<SettingMismatch>> {
+        let actual = self.read_profile(profile)?;
+
+        Ok(profile
+            .0
+            .iter()
+            .zip(actual.0)
+            // the module may answer with another case, e.g. for hex values
+            .filter(|((_, expected), (_, actual))| !expected.eq_ignore_ascii_case(actual))
+            .map(|((variable, expected), (_, actual))| SettingMismatch {
+                variable: variable.clone(),
+                expected: expected.clone(),
+                actual,
+            })
+            .collect())
+    }
+
+    fn restore_profile(&mut self, snapshot: &Profile, save: bool) -> Result<()> {
+        for (variable, value) in &snapshot.0 {
+            self.set(variable, value)?;
+        }
+
+        if save {
+            self.save()?;
+        }
+
+        info!("Previous configuration restored");
+
+        Ok(())
+    }
+}
+
+#[test]
+fn settings_error_display_1() {
+    let e = SettingsError::Rejected(vec![
+        SettingFailure {
+            setting: "set bl p m 0".to_string(),
+            code: ResponseCodes::UnknownVariableOrOption,
+        },
+        SettingFailure {
+            setting: "set sy d n JugglerBGX".to_string(),
+            code: ResponseCodes::InvalidArgument,
+        },
+    ]);
+
+    assert_eq!(
+        e.to_string(),
+        "Module rejected 2 setting(s): \"set bl p m 0\" -> UnknownVariableOrOption, \"set sy d n JugglerBGX\" -> InvalidArgument"
+    );
+}
+
+#[test]
+fn profile_parse_1() {
+    const PROFILE: &str = "# sample\n\nsy d n = JugglerBGX\n  bl e p=any \n";
+
+    let p: Profile = PROFILE.parse().unwrap();
+    assert_eq!(
+        p,
+        Profile(vec![
+            ("sy d n".to_string(), "JugglerBGX".to_string()),
+            ("bl e p".to_string(), "any".to_string()),
+        ])
+    );
+    assert_eq!(p.to_string(), "sy d n = JugglerBGX\nbl e p = any\n");
+
+    assert!("sy d n JugglerBGX".parse::<Profile>().is_err());
+    assert!(" = JugglerBGX".parse::<Profile>().is_err());
+}
+
+#[test]
+fn apply_profile_verified_1() {
+    use crate::recording::Traffic;
+
+    let mut bgx = crate::recording::replay_opened([
+        // snapshot
+        Traffic::Sent(b"get sy d n\r\n".to_vec()),
+        Traffic::Received(b"R000005\r\nold\r\n".to_vec()),
+        Traffic::Sent(b"set sy d n new\r\n".to_vec()),
+        Traffic::Received(b"R000000\r\n".to_vec()),
+        // the module truncated the value
+        Traffic::Sent(b"get sy d n\r\n".to_vec()),
+        Traffic::Received(b"R000004\r\nne\r\n".to_vec()),
+        // nothing has been saved, so setting the old value is enough
+        Traffic::Sent(b"set sy d n old\r\n".to_vec()),
+        Traffic::Received(b"R000000\r\n".to_vec()),
+    ]);
+    let profile = Profile(vec![("sy d n".to_string(), "new".to_string())]);
+
+    let e = bgx.apply_profile_verified(&profile, false).unwrap_err();
+    assert_eq!(
+        e.downcast_ref::<SettingsError>(),
+        Some(&SettingsError::Mismatch(vec![SettingMismatch {
+            variable: "sy d n".to_string(),
+            expected: "new".to_string(),
+            actual: "ne".to_string(),
+        }]))
+    );
+    // all recorded commands have been sent
+    assert!(bgx.write_all_with_timeout(b"save\r\n", None).is_err());
+}
@@ -0,0 +1,32 @@
+use crate::mac::Mac;
+
+/// state of the module as far as it is known by the host
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ModuleState {
+    /// nothing is known yet, e.g. after opening the port or after an error
+    #[default]
+    Unknown,
+    /// module accepts commands and has no active connection
+    Command,
+    /// module forwards data but no connection is known
+    Stream,
+    /// module is connected to the given peer and forwards data
+    Connected(Mac),
+    /// commands are forwarded to the given peer whose answers carry headers, the break sequence leads back
+    Remote(Mac),
+    /// module accepts commands while the connection to the given peer stays open, e.g. after a break
+    Linked(Mac),
+    /// a scan is running, module accepts commands
+    Scanning,
+    /// module sleeps without an active connection and has to be woken up before commands
+    Asleep,
+}
+
+/// how the module frames its answers in command mode
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProtocolMode {
+    /// each answer starts with a header containing the response code and the length
+    Machine,
+    /// commands are echoed and answers are plain text lines
+    Human,
+}
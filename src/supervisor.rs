@@ -0,0 +1,303 @@
+use anyhow::{anyhow, Result};
+use log::{debug, info, warn};
+use std::{
+    sync::{
+        mpsc::{channel, RecvTimeoutError, Sender},
+        Arc, Mutex, MutexGuard,
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use crate::{bgx::Bgx13p, con_param::ConInfo, mac::Mac};
+
+/// every n-th check requests the connection even if the module didn't leave the stream mode
+pub const ACTIVE_CHECK_EVERY: u32 = 10;
+
+/// states of a supervised link which are reported to the callback
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LinkState {
+    /// trying to (re)connect, attempt starts at 1
+    Connecting {
+        attempt: u32,
+    },
+    Connected,
+    /// link dropped or connection attempt failed, waiting before the next attempt
+    Disconnected {
+        retry_in: Duration,
+    },
+    /// supervisor has been stopped and the link has been closed
+    Stopped,
+}
+
+/// exponential backoff between reconnection attempts
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Backoff {
+    pub initial: Duration,
+    pub max: Duration,
+    pub multiplier: u32,
+    /// fraction of the delay which is randomly added, 0.0 to 1.0
+    pub jitter: f64,
+}
+
+impl Default for Backoff {
+    fn default() -> Self {
+        Self {
+            initial: Duration::from_millis(500),
+            max: Duration::from_secs(30),
+            multiplier: 2,
+            jitter: 0.2,
+        }
+    }
+}
+
+impl Backoff {
+    /// delay before the given attempt (starting at 1) without jitter
+    pub fn delay(&self, attempt: u32) -> Duration {
+        let factor = self
+            .multiplier
+            .checked_pow(attempt.saturating_sub(1))
+            .unwrap_or(u32::MAX);
+
+        self.initial.saturating_mul(factor).min(self.max)
+    }
+
+    /// delay before the given attempt with a random jitter added
+    pub fn delay_with_jitter(&self, attempt: u32) -> Duration {
+        let delay = self.delay(attempt);
+
+        // a cheap random source is sufficient to avoid reconnecting in lockstep
+        let random = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.subsec_nanos() % 1000)
+            .unwrap_or_default();
+
+        delay + delay.mul_f64(self.jitter.clamp(0.0, 1.0) * f64::from(random) / 1000.0)
+    }
+}
+
+/**
+    Keeps a link to a target module up in a background thread.
+    Every `check_interval` it's checked without writing whether the module left the stream mode, e.g. with an event of the dropped link.
+    Only then, or every [`ACTIVE_CHECK_EVERY`] checks for drops without an event, the connection is requested with a break.
+    The module is accessible in between via [`LinkSupervisor::module`], received data stays available to its reads.
+*/
+pub struct LinkSupervisor {
+    bgx: Arc<Mutex<Bgx13p>>,
+    stop: Sender<()>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl LinkSupervisor {
+    pub fn start(
+        bgx: Bgx13p,
+        target: Mac,
+        backoff: Backoff,
+        check_interval: Duration,
+        mut on_state: impl FnMut(LinkState) + Send + 'static,
+    ) -> Self {
+        let bgx = Arc::new(Mutex::new(bgx));
+        let (stop, stop_rx) = channel();
+
+        let handle = {
+            let bgx = bgx.clone();
+            thread::spawn(move || {
+                let mut attempt = 0;
+                let mut connected = false;
+                let mut passive_checks = 0;
+
+                loop {
+                    let check = lock(&bgx).and_then(|mut b| {
+                        passive_checks += 1;
+                        if b.left_stream_mode()? || passive_checks >= ACTIVE_CHECK_EVERY {
+                            passive_checks = 0;
+                            link_alive(&mut b, &target)
+                        } else {
+                            Ok(true)
+                        }
+                    });
+                    let wait = match check {
+                        Ok(true) => check_interval,
+                        res => {
+                            if let Err(e) = res {
+                                debug!("Checking link to {target} failed: {e}");
+                            }
+
+                            if connected {
+                                warn!("Link to {target} dropped");
+                                connected = false;
+                                on_state(LinkState::Disconnected {
+                                    retry_in: Duration::ZERO,
+                                });
+                            }
+
+                            attempt += 1;
+                            on_state(LinkState::Connecting { attempt });
+
+                            match lock(&bgx).and_then(|mut b| b.connect(&target)) {
+                                Ok(_) => {
+                                    info!("Link to {target} established");
+                                    attempt = 0;
+                                    connected = true;
+                                    on_state(LinkState::Connected);
+                                    check_interval
+                                }
+                                Err(e) => {
+                                    let retry_in = backoff.delay_with_jitter(attempt);
+                                    warn!(
+                                        "Couldn't connect to {target}: {e}, retry in {retry_in:?}"
+                                    );
+                                    on_state(LinkState::Disconnected { retry_in });
+                                    retry_in
+                                }
+                            }
+                        }
+                    };
+
+                    match stop_rx.recv_timeout(wait) {
+                        Err(RecvTimeoutError::Timeout) => continue,
+                        _ => break,
+                    }
+                }
+
+                if let Err(e) = lock(&bgx).and_then(|mut b| b.disconnect()) {
+                    warn!("Couldn't disconnect from {target} on stop: {e}");
+                }
+                on_state(LinkState::Stopped);
+            })
+        };
+
+        Self {
+            bgx,
+            stop,
+            handle: Some(handle),
+        }
+    }
+
+    /// locks the module, e.g. to exchange data while the link is up
+    pub fn module(&self) -> Result<MutexGuard<'_, Bgx13p>> {
+        lock(&self.bgx)
+    }
+
+    /// stops supervising, closes the link and hands back the module
+    pub fn stop(mut self) -> Result<Bgx13p> {
+        self.shutdown()?;
+
+        let bgx = self.bgx.clone();
+        drop(self);
+
+        Arc::try_unwrap(bgx)
+            .map_err(|_| anyhow!("Module is still borrowed"))?
+            .into_inner()
+            .map_err(|_| anyhow!("Module lock is poisoned"))
+    }
+
+    fn shutdown(&mut self) -> Result<()> {
+        if let Some(handle) = self.handle.take() {
+            let _ = self.stop.send(());
+            handle
+                .join()
+                .map_err(|_| anyhow!("Supervisor thread panicked"))?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Drop for LinkSupervisor {
+    fn drop(&mut self) {
+        if let Err(e) = self.shutdown() {
+            warn!("Couldn't stop link supervisor: {e}");
+        }
+    }
+}
+
+fn lock(bgx: &Mutex<Bgx13p>) -> Result<MutexGuard<'_, Bgx13p>> {
+    bgx.lock().map_err(|_| anyhow!("Module lock is poisoned"))
+}
+
+/// checks whether the module is still connected to the target and returns to stream mode if so
+pub(crate) fn link_alive(bgx: &mut Bgx13p, target: &Mac) -> Result<bool> {
+    bgx.switch_to_command_mode()?;
+
+    let alive = matches!(bgx.con_info()?, Some(ConInfo(mac)) if mac == *target);
+    if alive {
+        bgx.enter_stream_mode(*target)?;
+    }
+
+    Ok(alive)
+}
+
+#[test]
+fn backoff_delay_1() {
+    let b = Backoff {
+        initial: Duration::from_millis(100),
+        max: Duration::from_secs(1),
+        multiplier: 2,
+        jitter: 0.5,
+    };
+
+    assert_eq!(b.delay(1), Duration::from_millis(100));
+    assert_eq!(b.delay(2), Duration::from_millis(200));
+    assert_eq!(b.delay(4), Duration::from_millis(800));
+    assert_eq!(b.delay(5), Duration::from_secs(1));
+    assert_eq!(b.delay(100), Duration::from_secs(1));
+
+    let d = b.delay_with_jitter(3);
+    assert!(d >= Duration::from_millis(400) && d <= Duration::from_millis(600));
+}
+
+#[test]
+fn link_supervisor_1() {
+    use crate::{recording::Traffic, state::ModuleState};
+
+    let no_connection = || {
+        [
+            Traffic::Sent(b"con params\r\n".to_vec()),
+            Traffic::Received(b"R000031\r\n!  Param Value\r\n#  Err   0208\r\n".to_vec()),
+            Traffic::ReadTimeouts(1),
+        ]
+    };
+    let traffic = no_connection().into_iter().chain([
+        Traffic::Sent(b"con d0cf5e828506 2\r\n".to_vec()),
+        Traffic::Received(b"R000000\r\n".to_vec()),
+        Traffic::ReadTimeouts(1),
+        // the link drops silently, so only the active check notices it
+        Traffic::Sent(b"$$$".to_vec()),
+        Traffic::ReadTimeouts(1),
+        Traffic::Sent(b"\r\n\r\n".to_vec()),
+        Traffic::Received(b"Ready\r\n".to_vec()),
+        Traffic::ReadTimeouts(1),
+    ]);
+    let bgx = crate::recording::replay_opened(traffic.chain(no_connection()));
+    let target = "d0cf5e828506".parse().unwrap();
+
+    let (tx, rx) = channel();
+    let backoff = Backoff {
+        initial: Duration::from_secs(60),
+        ..Default::default()
+    };
+    let supervisor =
+        LinkSupervisor::start(bgx, target, backoff, Duration::from_millis(5), move |s| {
+            let _ = tx.send(s);
+        });
+
+    let states = (0..4)
+        .map(|_| rx.recv_timeout(Duration::from_secs(5)).unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        states,
+        vec![
+            LinkState::Connecting { attempt: 1 },
+            LinkState::Connected,
+            LinkState::Disconnected {
+                retry_in: Duration::ZERO
+            },
+            LinkState::Connecting { attempt: 1 },
+        ]
+    );
+
+    // the reconnection failed as the replay is over, the drop has been noticed by the host
+    let bgx = supervisor.stop().unwrap();
+    assert_eq!(bgx.state(), ModuleState::Command);
+}
@@ -0,0 +1,49 @@
+use anyhow::Result;
+use log::{info, warn};
+use std::{
+    net::{TcpListener, TcpStream},
+    time::Duration,
+};
+
+use crate::{bgx::Bgx13p, bridge::BridgeEnd, mac::Mac};
+
+impl Bgx13p {
+    /**
+        Accepts TCP clients one after the other and bridges each of them to the target, see [`Bgx13p::bridge_tcp_client`].
+        Further clients wait in the backlog of the listener until the current one left.
+    */
+    pub fn serve_tcp(
+        &mut self,
+        listener: &TcpListener,
+        target: &Mac,
+        idle_check: Duration,
+    ) -> Result<()> {
+        for client in listener.incoming() {
+            let client = client?;
+            let addr = client.peer_addr()?;
+            info!("TCP client {addr} connected");
+
+            match self.bridge_tcp_client(client, target, idle_check) {
+                Ok(end) => info!("Bridge for TCP client {addr} ended: {end:?}"),
+                Err(e) => warn!("Bridge for TCP client {addr} failed: {e:#}"),
+            }
+        }
+
+        Ok(())
+    }
+
+    /**
+        Connects to the target and pipes bytes between the client and the link in stream mode,
+        see [`Bgx13p::bridge`].
+    */
+    pub fn bridge_tcp_client(
+        &mut self,
+        mut client: TcpStream,
+        target: &Mac,
+        idle_check: Duration,
+    ) -> Result<BridgeEnd> {
+        client.set_read_timeout(Some(self.timeouts.common))?;
+
+        self.bridge(&mut client, target, idle_check)
+    }
+}
@@ -0,0 +1,319 @@
+use anyhow::Result;
+use crossterm::{
+    event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers},
+    terminal,
+};
+use log::debug;
+use rustyline::{
+    completion::Completer, error::ReadlineError, highlight::Highlighter, hint::Hinter,
+    history::DefaultHistory, validate::Validator, Cmd, ConditionalEventHandler, Editor,
+    EventContext, EventHandler, Helper, RepeatCount,
+};
+use std::{
+    io::{stdout, Write},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+};
+
+use crate::{
+    bgx::Bgx13p,
+    command::{BgxCommand, Command},
+    con_param::ConInfo,
+    response::{BgxResponse, ResponseCodes},
+    state::ModuleState,
+};
+
+/// commands offered for completion at the start of a line
+const COMMANDS: [&str; 18] = [
+    "adv high",
+    "adv low",
+    "adv off",
+    "clrb",
+    "con",
+    "con params",
+    "dct",
+    "fac",
+    "get",
+    "reboot",
+    "rmt",
+    "save",
+    "scan",
+    "scan results",
+    "set",
+    "sleep",
+    "str",
+    "ver",
+];
+
+/// variables offered for completion after get and set
+const VARIABLES: [&str; 16] = [
+    "bl a", "bl e p", "bl p m", "bl p p", "bl v h d", "bl v h i", "bl v l d", "bl v l i", "sy b v",
+    "sy c m", "sy d n", "sy r e", "sy s t", "sy u", "ua b", "ua f",
+];
+
+impl Bgx13p {
+    /**
+        Bridges stdin/stdout to the module until Ctrl-D is pressed.
+        In command mode each line is sent as command and the response is printed,
+        Ctrl-] switches to stream mode and back.
+    */
+    pub fn interactive_terminal(&mut self) -> Result<()> {
+        let toggle = Arc::new(AtomicBool::new(false));
+
+        let mut rl = Editor::<CommandHelper, DefaultHistory>::new()?;
+        rl.set_helper(Some(CommandHelper));
+        rl.bind_sequence(
+            rustyline::KeyEvent::ctrl(']'),
+            EventHandler::Conditional(Box::new(StreamHotkey(toggle.clone()))),
+        );
+
+        println!("Command mode, Ctrl-] toggles stream mode, Ctrl-D quits");
+
+        loop {
+            let line = match rl.readline("bgx> ") {
+                Ok(line) => line,
+                Err(ReadlineError::Interrupted) => continue,
+                Err(ReadlineError::Eof) => break,
+                Err(e) => return Err(e.into()),
+            };
+
+            if toggle.swap(false, Ordering::Relaxed) || line.trim() == "str" {
+                self.terminal_stream()?;
+                continue;
+            }
+
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+            rl.add_history_entry(line)?;
+
+            if let Err(e) = self.terminal_command(line) {
+                println!("Error: {e:#}");
+            }
+        }
+
+        Ok(())
+    }
+
+    /// sends a typed command and pretty prints the response
+    fn terminal_command(&mut self, line: &str) -> Result<()> {
+        let command = line.parse::<BgxCommand>().ok();
+        if let Some(c) = &command {
+            c.to_bytes()?;
+        }
+
+        self.switch_to_command_mode()?;
+        self.write_line(line.as_bytes(), None)?;
+
+        let res = self.read_complete_response(self.timeouts.interactive);
+
+        // the module may have left the command mode or changed its connection on its own
+        let succeeded = matches!(&res, Ok((h, _)) if h.response_code == ResponseCodes::Success);
+        self.track_command(command.as_ref(), succeeded);
+
+        let (h, ans) = res?;
+        let code = match h.response_code {
+            ResponseCodes::Success => h.response_code.to_string(),
+            c => format!("{c} ({})", c as u8),
+        };
+        println!("{code}, {} bytes", h.data_length);
+        for l in ans.lines() {
+            println!("  {l}");
+        }
+
+        Ok(())
+    }
+
+    /// forwards keys and received data in stream mode until Ctrl-] is pressed
+    fn terminal_stream(&mut self) -> Result<()> {
+        self.switch_to_command_mode()?;
+
+        match self.con_info() {
+            Ok(Some(ConInfo(peer))) => self.enter_stream_mode(peer)?,
+            res => {
+                if let Err(e) = res {
+                    debug!("Couldn't request connection info: {e}");
+                }
+                self.write_expect_success(&BgxCommand::StreamMode, None)?;
+                self.state = ModuleState::Stream;
+            }
+        }
+        println!("Stream mode, Ctrl-] returns to command mode");
+
+        let forwarded = {
+            let _raw = RawMode::enable()?;
+            self.forward_stream()
+        };
+
+        println!();
+        forwarded?;
+        self.switch_to_command_mode()
+    }
+
+    fn forward_stream(&mut self) -> Result<()> {
+        let mut out = stdout();
+
+        loop {
+            if event::poll(self.timeouts.common)? {
+                if let Event::Key(key) = event::read()? {
+                    if key.kind == KeyEventKind::Release {
+                        continue;
+                    }
+                    if is_stream_hotkey(&key) {
+                        return Ok(());
+                    }
+                    if let Some(bytes) = key_bytes(&key) {
+                        self.write_all_with_timeout(&bytes, None)?;
+                    }
+                }
+            }
+
+            match self.read_bgx_response(self.timeouts.common)? {
+                BgxResponse::DataWithoutHeader(d) => out.write_all(&raw_newlines(&d))?,
+                BgxResponse::DataWithHeader(h, ans) => {
+                    write!(out, "\r\n[{}] {}\r\n", h.response_code, ans.trim_end())?
+                }
+            }
+            out.flush()?;
+        }
+    }
+}
+
+/// completes commands and the variables of get and set
+struct CommandHelper;
+
+impl Completer for CommandHelper {
+    type Candidate = String;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _ctx: &rustyline::Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<String>)> {
+        Ok((0, complete_command(line.get(..pos).unwrap_or(line))))
+    }
+}
+
+impl Hinter for CommandHelper {
+    type Hint = String;
+}
+
+impl Highlighter for CommandHelper {}
+
+impl Validator for CommandHelper {}
+
+impl Helper for CommandHelper {}
+
+/// accepts the current line and marks that stream mode has been requested
+struct StreamHotkey(Arc<AtomicBool>);
+
+impl ConditionalEventHandler for StreamHotkey {
+    fn handle(
+        &self,
+        _evt: &rustyline::Event,
+        _n: RepeatCount,
+        _positive: bool,
+        _ctx: &EventContext,
+    ) -> Option<Cmd> {
+        self.0.store(true, Ordering::Relaxed);
+        Some(Cmd::AcceptLine)
+    }
+}
+
+/// keeps the terminal in raw mode as long as it lives
+struct RawMode;
+
+impl RawMode {
+    fn enable() -> Result<Self> {
+        terminal::enable_raw_mode()?;
+
+        Ok(Self)
+    }
+}
+
+impl Drop for RawMode {
+    fn drop(&mut self) {
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+/// complete lines starting with the given input
+fn complete_command(input: &str) -> Vec<String> {
+    match input.split_at_checked(4) {
+        Some((cmd @ ("get " | "set "), variable)) => VARIABLES
+            .iter()
+            .filter(|v| v.starts_with(variable))
+            .map(|v| match cmd {
+                // the value follows
+                "set " => format!("{cmd}{v} "),
+                _ => format!("{cmd}{v}"),
+            })
+            .collect(),
+        _ => COMMANDS
+            .iter()
+            .filter(|c| c.starts_with(input))
+            .map(ToString::to_string)
+            .collect(),
+    }
+}
+
+// terminals send Ctrl-] as 0x1d which is reported as Ctrl-5 in raw mode
+fn is_stream_hotkey(key: &KeyEvent) -> bool {
+    key.modifiers.contains(KeyModifiers::CONTROL)
+        && matches!(key.code, KeyCode::Char(']') | KeyCode::Char('5'))
+}
+
+/// bytes to send for a key pressed in stream mode
+fn key_bytes(key: &KeyEvent) -> Option<Vec<u8>> {
+    match key.code {
+        KeyCode::Char(c) if key.modifiers.contains(KeyModifiers::CONTROL) => c
+            .is_ascii_alphabetic()
+            .then(|| vec![c.to_ascii_uppercase() as u8 - b'@']),
+        KeyCode::Char(c) => Some(c.to_string().into_bytes()),
+        KeyCode::Enter => Some(Command::LINEBREAK.to_vec()),
+        KeyCode::Tab => Some(b"\t".to_vec()),
+        KeyCode::Backspace => Some(vec![0x08]),
+        KeyCode::Esc => Some(vec![0x1b]),
+        _ => None,
+    }
+}
+
+/// a terminal in raw mode needs a carriage return before each line feed
+fn raw_newlines(data: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(data.len());
+    let mut prev = 0;
+
+    for &b in data {
+        if b == b'\n' && prev != b'\r' {
+            out.push(b'\r');
+        }
+        out.push(b);
+        prev = b;
+    }
+
+    out
+}
+
+#[test]
+fn complete_command_1() {
+    assert_eq!(
+        complete_command("adv"),
+        vec!["adv high", "adv low", "adv off"]
+    );
+    assert_eq!(complete_command("con"), vec!["con", "con params"]);
+    assert_eq!(complete_command("set sy d"), vec!["set sy d n "]);
+    assert_eq!(
+        complete_command("get bl v h"),
+        vec!["get bl v h d", "get bl v h i"]
+    );
+    assert!(complete_command("xyz").is_empty());
+}
+
+#[test]
+fn raw_newlines_1() {
+    assert_eq!(raw_newlines(b"a\nb\r\nc"), b"a\r\nb\r\nc".to_vec());
+}
@@ -0,0 +1,136 @@
+use std::time::Duration;
+
+use crate::{bgx::Bgx13p, command::Command};
+
+/// timeouts of the communication with the module, see [`Bgx13p::set_timeouts`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Timeouts {
+    /// silence after which the module is considered done with an answer, also used between single reads
+    pub common: Duration,
+    /// how long the module tries to connect to a peer, it's passed rounded up to whole seconds but at least one
+    pub connect: Duration,
+    pub disconnect: Duration,
+    /// answers to get, set and save
+    pub settings: Duration,
+    /// blocked writes with hardware flow control, only a stalled module is detected
+    pub flow_control_stall: Duration,
+    /// boot banner after a reboot or a factory reset
+    pub boot: Duration,
+    /// answers of a remote module which travel over the BLE link and back
+    pub remote: Duration,
+    /// answers to commands typed in the interactive terminal
+    pub interactive: Duration,
+    /// how long the module scans before its results are read
+    pub scan: Duration,
+}
+
+impl Default for Timeouts {
+    fn default() -> Self {
+        Self {
+            common: Command::TIMEOUT_COMMON,
+            connect: Duration::from_secs(Command::TIMEOUT_CONNECT_BGX_INTERN),
+            disconnect: Command::TIMEOUT_DISCONNECT,
+            settings: Command::TIMEOUT_SETTINGS,
+            flow_control_stall: Command::TIMEOUT_FLOW_CONTROL_STALL,
+            boot: Command::TIMEOUT_BOOT,
+            remote: Command::TIMEOUT_REMOTE,
+            interactive: Command::TIMEOUT_INTERACTIVE,
+            scan: Command::TIMEOUT_SCAN,
+        }
+    }
+}
+
+impl Timeouts {
+    /// connect timeout as passed to the module, which doesn't accept 0
+    pub(crate) fn connect_secs(&self) -> u64 {
+        let secs = self.connect.as_secs() + u64::from(self.connect.subsec_nanos() > 0);
+
+        secs.max(1)
+    }
+}
+
+/// options of [`Bgx13p::connect_with`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ConnectOptions {
+    /// deadline of the whole call including leaving the stream mode and disconnecting an old link
+    pub timeout: Duration,
+    /// clears the bondings if the peer doesn't match them anymore, the call fails anyway
+    pub clear_bondings_on_mismatch: bool,
+}
+
+impl Default for ConnectOptions {
+    /// enough for all retries to leave the stream mode and a connection attempt of a slow peer
+    fn default() -> Self {
+        Self {
+            timeout: Duration::from_secs(10),
+            clear_bondings_on_mismatch: true,
+        }
+    }
+}
+
+impl Bgx13p {
+    pub fn timeouts(&self) -> Timeouts {
+        self.timeouts
+    }
+
+    /// changes the timeouts of all following calls
+    pub fn set_timeouts(&mut self, timeouts: Timeouts) {
+        self.timeouts = timeouts;
+    }
+}
+
+#[test]
+fn connect_secs_1() {
+    let secs = |connect| {
+        Timeouts {
+            connect,
+            ..Default::default()
+        }
+        .connect_secs()
+    };
+
+    assert_eq!(secs(Duration::ZERO), 1);
+    assert_eq!(secs(Duration::from_millis(500)), 1);
+    assert_eq!(secs(Duration::from_secs(2)), 2);
+    assert_eq!(secs(Duration::from_millis(2001)), 3);
+}
+
+#[test]
+fn connect_with_1() {
+    use crate::{recording::Traffic, state::ModuleState};
+
+    let mut bgx = crate::recording::replay_opened([
+        Traffic::Sent(b"con d0cf5e828506 2\r\n".to_vec()),
+        Traffic::Received(b"R000000\r\n".to_vec()),
+    ]);
+    let mac = "d0cf5e828506".parse().unwrap();
+
+    // the module gets the time which is left after its answer
+    let options = ConnectOptions {
+        timeout: Duration::from_millis(3000),
+        ..Default::default()
+    };
+    bgx.connect_with(&mac, options).unwrap();
+    assert_eq!(bgx.state(), ModuleState::Connected(mac));
+
+    bgx.state = ModuleState::Command;
+    let options = ConnectOptions {
+        timeout: Duration::from_millis(1200),
+        ..Default::default()
+    };
+    // less than a second is left for the module after its answer
+    let e = bgx.connect_with(&mac, options).unwrap_err();
+    assert!(e.to_string().starts_with("No time left"));
+
+    // leaving the stream mode of the old link doesn't fit into the deadline, so nothing is sent
+    bgx.state = ModuleState::Connected(mac);
+    let options = ConnectOptions {
+        timeout: Duration::from_millis(1000),
+        ..Default::default()
+    };
+    let e = bgx
+        .connect_with(&"d0cf5e828507".parse().unwrap(), options)
+        .unwrap_err();
+    assert!(e.to_string().starts_with("Deadline of the call passed"));
+    assert_eq!(bgx.state(), ModuleState::Unknown);
+}